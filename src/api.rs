@@ -0,0 +1,816 @@
+use std::{
+    path::{Path as FsPath, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    middleware,
+    response::{
+        IntoResponse, Response,
+        sse::{Event as SseEvent, KeepAlive, Sse},
+    },
+    routing::{delete, get, post},
+};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use tokio_stream::{
+    Stream, StreamExt,
+    wrappers::{BroadcastStream, errors::BroadcastStreamRecvError},
+};
+use tower_http::{
+    compression::CompressionLayer,
+    services::{ServeDir, ServeFile},
+};
+use tracing::info;
+
+use crate::{
+    AppState,
+    auth::{Identity, Role, TOKEN_COOKIE, require_admin, require_identity, require_member},
+    cache::CacheEntry,
+    config::Scheduling,
+    error::RequestError,
+    format::MinHeight,
+    history::HistoryEntry,
+    job::{self, Clip, JobOptions, Vote},
+    meta::InspectMetadata,
+    player::{PlayerStatus, RpcCommand},
+    yt_dlp::{ErrorCode, PlaylistRange, SearchResult, Video, YtDlpError},
+};
+
+/// The API routes, falling back to the UI in `static_dir` for everything else.
+pub fn router(state: Arc<AppState>, static_dir: &FsPath) -> Router {
+    let serve_app =
+        ServeDir::new(static_dir).not_found_service(ServeFile::new(static_dir.join("index.html")));
+
+    // guests may only add to the queue and look at it
+    let guest = Router::new()
+        .route("/api/queue_merged", post(queue_merged_handler))
+        .route("/api/queue_split", post(queue_split_handler))
+        .route("/api/queue_file", post(queue_file_handler))
+        .route("/api/queue_playlist", post(queue_playlist_handler))
+        .route("/api/inspect", get(inspect_handler))
+        .route("/api/events", get(events_handler))
+        .route("/api/jobs/{id}", get(get_job_handler))
+        .route("/api/history", get(get_history))
+        .route("/api/search", get(search_handler))
+        .route("/api/vote/{id}", post(vote_handler))
+        .route("/api/vote_skip", post(vote_skip_handler))
+        .route("/api/me", get(me_handler));
+
+    // members manage their own jobs, the handlers check who queued them
+    let member = Router::new()
+        .route("/api/cancel", post(cancel_current_handler))
+        .route("/api/cancel/{id}", post(cancel_id_handler))
+        .route("/api/cancel_batch/{id}", post(cancel_batch_handler))
+        .route("/api/jobs/{id}/retry", post(retry_job_handler))
+        .route("/api/move/{id}/{new_pos}", post(move_to))
+        .route_layer(middleware::from_fn(require_member));
+
+    let admin = Router::new()
+        .route("/api/clear", post(clear_handler))
+        .route("/api/execute_command", post(player_commands))
+        .route("/api/swap/{id}", post(swap))
+        .route("/api/remove_history", post(remove_history_entry))
+        .route("/api/cache", get(cache_handler).delete(purge_cache_handler))
+        .route("/api/cache/{name}", delete(remove_cached_handler))
+        .route_layer(middleware::from_fn(require_admin));
+
+    let api = guest
+        .merge(member)
+        .merge(admin)
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_identity,
+        ));
+
+    Router::new()
+        .route("/api/login", post(login_handler))
+        .route("/api/logout", post(logout_handler))
+        .merge(api)
+        .layer(CompressionLayer::new())
+        .with_state(state)
+        .fallback_service(serve_app)
+}
+
+#[derive(Deserialize)]
+struct QueuePayload {
+    url: String,
+    height: Option<u32>,
+    // seconds into the video, taken from timestamps in the url if missing
+    start: Option<u32>,
+    end: Option<u32>,
+    // start where the video was left off last time, unless `start` is given
+    #[serde(default)]
+    resume: bool,
+    // subtitle language instead of the configured ones, empty for none
+    subtitles: Option<String>,
+}
+
+impl QueuePayload {
+    async fn options(&self, state: &AppState) -> Result<JobOptions, AppError> {
+        let start = match self.start {
+            None if self.resume => state.queue.resume_position(&self.url).await,
+            start => start,
+        };
+        let clip = Clip::new(&self.url, start, self.end)
+            .map_err(|e| AppError(StatusCode::BAD_REQUEST, e))?;
+        Ok(JobOptions {
+            clip,
+            subtitles: self.subtitles.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct QueueResponse {
+    job_id: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    batch_id: Option<usize>,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum StreamMode {
+    #[default]
+    Merged,
+    Split,
+}
+
+fn stream_job_type(mode: StreamMode, url: String, height: MinHeight) -> job::JobType {
+    // the format is filled in once the job has been resolved
+    match mode {
+        StreamMode::Merged => job::JobType::QueueMerged {
+            url,
+            height,
+            format_id: String::new(),
+        },
+        StreamMode::Split => job::JobType::QueueSplit {
+            url,
+            height,
+            format_id: String::new(),
+        },
+    }
+}
+
+async fn queue_stream_or_playlist(
+    state: &AppState,
+    identity: Identity,
+    mode: StreamMode,
+    payload: QueuePayload,
+) -> Result<Json<QueueResponse>, AppError> {
+    if Video::is_playlist(&payload.url) {
+        if payload.start.is_some()
+            || payload.end.is_some()
+            || payload.resume
+            || payload.subtitles.is_some()
+        {
+            return Err(RequestError::invalid(
+                "start, end, resume and subtitles cannot be used with playlists",
+            )
+            .into());
+        }
+        let playlist = queue_playlist(
+            state,
+            identity,
+            QueuePlaylistPayload {
+                url: payload.url,
+                height: payload.height,
+                mode,
+                start: None,
+                end: None,
+                reverse: false,
+                shuffle: false,
+            },
+        )
+        .await?;
+        let job_id = *playlist
+            .job_ids
+            .first()
+            .ok_or_else(|| RequestError::not_found("no playlist entries could be queued"))?;
+        return Ok(Json(QueueResponse {
+            job_id,
+            batch_id: Some(playlist.batch_id),
+        }));
+    }
+
+    check_pending_limit(state, &identity, 1).await?;
+
+    let url = payload.url.clone();
+    let options = JobOptions {
+        submitter: identity.name,
+        ..payload.options(state).await?
+    };
+    let height = payload
+        .height
+        .map(MinHeight)
+        .unwrap_or(state.default_height);
+    let job_id = state
+        .queue
+        .submit(stream_job_type(mode, payload.url, height), options)
+        .await;
+
+    info!("queued {url} with job_id {job_id}");
+
+    Ok(Json(QueueResponse {
+        job_id,
+        batch_id: None,
+    }))
+}
+
+async fn queue_merged_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(payload): Json<QueuePayload>,
+) -> Result<Json<QueueResponse>, AppError> {
+    queue_stream_or_playlist(&state, identity, StreamMode::Merged, payload).await
+}
+
+async fn queue_split_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(payload): Json<QueuePayload>,
+) -> Result<Json<QueueResponse>, AppError> {
+    queue_stream_or_playlist(&state, identity, StreamMode::Split, payload).await
+}
+
+#[derive(Deserialize)]
+struct QueuePlaylistPayload {
+    url: String,
+    height: Option<u32>,
+    #[serde(default)]
+    mode: StreamMode,
+    // 1-based and inclusive, like yt-dlp's --playlist-items
+    start: Option<usize>,
+    end: Option<usize>,
+    #[serde(default)]
+    reverse: bool,
+    #[serde(default)]
+    shuffle: bool,
+}
+
+#[derive(Serialize)]
+struct QueuePlaylistResponse {
+    batch_id: usize,
+    job_ids: Vec<usize>,
+}
+
+async fn queue_playlist(
+    state: &AppState,
+    identity: Identity,
+    payload: QueuePlaylistPayload,
+) -> Result<QueuePlaylistResponse, AppError> {
+    if payload.start == Some(0) || payload.end == Some(0) {
+        return Err(RequestError::invalid("playlist entries are counted from 1").into());
+    }
+    if let (Some(start), Some(end)) = (payload.start, payload.end)
+        && end < start
+    {
+        return Err(RequestError::invalid(format!(
+            "the playlist range {start}-{end} ends before it starts"
+        ))
+        .into());
+    }
+
+    info!("expanding playlist {}...", payload.url);
+
+    let range = PlaylistRange {
+        start: payload.start,
+        end: payload.end,
+    };
+    let mut entries = {
+        let slot = state.video.lookup_slot().await?;
+        state.video.get_playlist(&payload.url, range, &slot).await?
+    };
+    if entries.is_empty() {
+        return Err(
+            RequestError::not_found(format!("playlist {} has no entries", payload.url)).into(),
+        );
+    }
+    check_pending_limit(state, &identity, entries.len()).await?;
+
+    if payload.shuffle {
+        entries.shuffle(&mut rand::rng());
+    } else if payload.reverse {
+        entries.reverse();
+    }
+
+    let height = payload
+        .height
+        .map(MinHeight)
+        .unwrap_or(state.default_height);
+    let batch_id = state.queue.new_batch();
+    let jobs = entries
+        .into_iter()
+        .map(|entry| {
+            let options = JobOptions {
+                title: entry.title,
+                batch_id: Some(batch_id),
+                submitter: identity.name.clone(),
+                ..Default::default()
+            };
+            (stream_job_type(payload.mode, entry.url, height), options)
+        })
+        .collect();
+    let job_ids = state.queue.submit_all(jobs).await;
+
+    info!(
+        "queued {} entries of playlist {} as batch {batch_id}",
+        job_ids.len(),
+        payload.url
+    );
+
+    Ok(QueuePlaylistResponse { batch_id, job_ids })
+}
+
+async fn queue_playlist_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(payload): Json<QueuePlaylistPayload>,
+) -> Result<Json<QueuePlaylistResponse>, AppError> {
+    Ok(Json(queue_playlist(&state, identity, payload).await?))
+}
+
+async fn queue_file_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(payload): Json<QueuePayload>,
+) -> Result<Json<QueueResponse>, AppError> {
+    if Video::is_playlist(&payload.url) {
+        return Err(RequestError::invalid(
+            "playlists cannot be queued as files, queue them as streams",
+        )
+        .into());
+    }
+
+    let url = payload.url.clone();
+    info!("queueing {url}...");
+    check_pending_limit(&state, &identity, 1).await?;
+    let options = JobOptions {
+        submitter: identity.name,
+        ..payload.options(&state).await?
+    };
+
+    let height = payload
+        .height
+        .map(MinHeight)
+        .unwrap_or(state.default_height);
+
+    let job_id = state
+        .queue
+        .submit(
+            job::JobType::QueueFile {
+                url: payload.url,
+                height,
+                title: String::new(),
+                // the cache picks the file once the video id is known
+                file: PathBuf::new(),
+            },
+            options,
+        )
+        .await;
+
+    info!("queued {url} with job_id {job_id}");
+
+    Ok(Json(QueueResponse {
+        job_id,
+        batch_id: None,
+    }))
+}
+
+async fn cancel_current_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+) -> Result<&'static str, AppError> {
+    let (current, _, _) = state.queue.inspect().await;
+    let Some(job) = current else {
+        return Ok("nothing to cancel");
+    };
+    require_owner(&identity, &job)?;
+    // the job that was checked, even if the next one has started since
+    Ok(if state.queue.cancel_by_id(job.job_id).await {
+        "task cancelled"
+    } else {
+        "nothing to cancel"
+    })
+}
+
+async fn cancel_id_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(job_id): Path<usize>,
+) -> Result<&'static str, AppError> {
+    if let Some(job) = state.queue.get_job(job_id).await {
+        require_owner(&identity, &job)?;
+    }
+    Ok(if state.queue.cancel_by_id(job_id).await {
+        "task cancelled"
+    } else {
+        "not found"
+    })
+}
+
+#[derive(Serialize)]
+struct CancelBatchResponse {
+    cancelled: usize,
+}
+
+async fn cancel_batch_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(batch_id): Path<usize>,
+) -> Result<Json<CancelBatchResponse>, AppError> {
+    let (current, queue, _) = state.queue.inspect().await;
+    for job in current.iter().chain(&queue) {
+        if job.batch_id == Some(batch_id) {
+            require_owner(&identity, job)?;
+        }
+    }
+    let cancelled = state.queue.cancel_batch(batch_id).await;
+    Ok(Json(CancelBatchResponse { cancelled }))
+}
+
+async fn clear_handler(State(state): State<Arc<AppState>>) -> &'static str {
+    state.queue.clear().await;
+    "queue cleared"
+}
+
+#[derive(Serialize)]
+struct InspectResponse {
+    now_playing: Option<InspectMetadata>,
+    queue: Vec<InspectMetadata>,
+    // most recently finished first, including failed and cancelled jobs
+    finished: Vec<InspectMetadata>,
+    player: Option<PlayerStatus>,
+    skip_votes_needed: usize,
+}
+
+async fn inspect_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<InspectResponse>, AppError> {
+    let ((now_playing, queue, finished), player) =
+        tokio::join!(state.queue.inspect(), state.monitor.latest());
+
+    Ok(Json(InspectResponse {
+        now_playing,
+        queue,
+        finished,
+        player,
+        skip_votes_needed: skip_votes_needed(&state),
+    }))
+}
+
+async fn events_handler(
+    State(state): State<Arc<AppState>>,
+) -> Sse<impl Stream<Item = Result<SseEvent, axum::Error>>> {
+    let stream = BroadcastStream::new(state.events.subscribe()).map(|event| match event {
+        Ok(event) => SseEvent::default().event(event.name()).json_data(event),
+        // the client missed events and should refetch /api/inspect
+        Err(BroadcastStreamRecvError::Lagged(skipped)) => Ok(SseEvent::default()
+            .event("lagged")
+            .data(skipped.to_string())),
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+async fn get_job_handler(
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<usize>,
+) -> Result<Json<InspectMetadata>, StatusCode> {
+    state
+        .queue
+        .get_job(job_id)
+        .await
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn retry_job_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(job_id): Path<usize>,
+) -> Result<Json<QueueResponse>, AppError> {
+    check_pending_limit(&state, &identity, 1).await?;
+    let job_id = state.queue.retry(job_id, identity.name).await?;
+    Ok(Json(QueueResponse {
+        job_id,
+        batch_id: None,
+    }))
+}
+
+async fn player_commands(
+    State(state): State<Arc<AppState>>,
+    Json(command): Json<RpcCommand>,
+) -> Result<Json<bool>, AppError> {
+    let chapters = state.queue.current_chapters().await;
+    command.apply(state.player.as_ref(), &chapters).await?;
+    Ok(Json(true))
+}
+
+async fn swap(
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<usize>,
+) -> Result<Json<bool>, AppError> {
+    state.queue.swap_with_running(job_id).await?;
+
+    Ok(Json(true))
+}
+
+async fn move_to(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path((job_id, new_index)): Path<(usize, usize)>,
+) -> Result<Json<bool>, AppError> {
+    // moving a job ahead would skip the turns that fair and democratic scheduling hand out
+    if identity.role != Role::Admin && state.queue.scheduling() != Scheduling::Fifo {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "only admins can move jobs unless the queue is first come, first served",
+        ));
+    }
+    if let Some(job) = state.queue.get_job(job_id).await {
+        require_owner(&identity, &job)?;
+    }
+    state.queue.reorder_job(job_id, new_index).await?;
+
+    Ok(Json(true))
+}
+
+const DEFAULT_SEARCH_LIMIT: usize = 10;
+
+#[derive(Deserialize)]
+struct SearchParams {
+    q: String,
+    limit: Option<usize>,
+}
+
+async fn search_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<SearchResult>>, AppError> {
+    if params.q.trim().is_empty() {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            "q must not be empty",
+        ));
+    }
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .min(state.search.max_results());
+    Ok(Json(state.search.search(&params.q, limit).await?))
+}
+
+#[derive(Deserialize)]
+struct VotePayload {
+    /// `null` takes the vote back
+    vote: Option<Vote>,
+}
+
+async fn vote_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(job_id): Path<usize>,
+    Json(payload): Json<VotePayload>,
+) -> Result<Json<bool>, AppError> {
+    let voter = voter_name(identity)?;
+    state.queue.vote(job_id, voter, payload.vote).await?;
+    Ok(Json(true))
+}
+
+#[derive(Serialize)]
+struct VoteSkipResponse {
+    votes: usize,
+    needed: usize,
+    skipped: bool,
+}
+
+async fn vote_skip_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+) -> Result<Json<VoteSkipResponse>, AppError> {
+    let voter = voter_name(identity)?;
+    let needed = skip_votes_needed(&state);
+    let (votes, skipped) = state.queue.vote_skip(voter, needed).await?;
+    Ok(Json(VoteSkipResponse {
+        votes,
+        needed,
+        skipped,
+    }))
+}
+
+/// Votes are counted per name, so they need auth to tell the voters apart.
+fn voter_name(identity: Identity) -> Result<String, AppError> {
+    identity
+        .name
+        .ok_or_else(|| AppError::new(StatusCode::BAD_REQUEST, "voting needs auth to be enabled"))
+}
+
+fn skip_votes_needed(state: &AppState) -> usize {
+    let active = state.auth.active_users(state.votes.active_window());
+    state.votes.skip_votes_needed(active)
+}
+
+async fn get_history(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<HistoryEntry>>, AppError> {
+    let mut history_entries = state.queue.get_history().await;
+    history_entries.reverse();
+
+    Ok(Json(history_entries))
+}
+
+#[derive(Deserialize)]
+struct RemoveHistoryPayload {
+    webpage_url: String,
+}
+
+async fn remove_history_entry(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<RemoveHistoryPayload>,
+) -> Result<(), AppError> {
+    state
+        .queue
+        .remove_history_entry(&payload.webpage_url)
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct CacheResponse {
+    max_bytes: u64,
+    total_bytes: u64,
+    // most recently used first
+    entries: Vec<CacheEntry>,
+}
+
+async fn cache_handler(State(state): State<Arc<AppState>>) -> Json<CacheResponse> {
+    let entries = state.cache.entries().await;
+    Json(CacheResponse {
+        max_bytes: state.cache.max_bytes(),
+        total_bytes: entries.iter().map(|entry| entry.size).sum(),
+        entries,
+    })
+}
+
+#[derive(Serialize)]
+struct PurgeResponse {
+    purged: usize,
+}
+
+async fn purge_cache_handler(State(state): State<Arc<AppState>>) -> Json<PurgeResponse> {
+    let purged = state.queue.purge_cache().await;
+    Json(PurgeResponse { purged })
+}
+
+async fn remove_cached_handler(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<&'static str, AppError> {
+    match state.queue.remove_cached(&name).await {
+        Ok(true) => Ok("removed"),
+        Ok(false) => Err(AppError::new(StatusCode::NOT_FOUND, "not cached")),
+        Err(e) => Err(AppError(StatusCode::CONFLICT, e)),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LoginPayload {
+    Token { token: String },
+    Guest { name: String },
+}
+
+/// Checks the token (the admin PIN or a user token), or lets a guest join under a
+/// name, and stores the token in a cookie.
+async fn login_handler(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<LoginPayload>,
+) -> Result<Response, AppError> {
+    if !state.auth.enabled() {
+        return Ok(Json(Identity::anonymous()).into_response());
+    }
+    let (token, identity) = match payload {
+        LoginPayload::Token { token } => {
+            let identity = state
+                .auth
+                .identify(&token)
+                .ok_or_else(|| AppError::new(StatusCode::UNAUTHORIZED, "invalid token"))?;
+            (token, identity)
+        }
+        LoginPayload::Guest { name } => state
+            .auth
+            .join_as_guest(&name)
+            .map_err(|e| AppError(StatusCode::CONFLICT, e))?
+            .ok_or_else(|| AppError::new(StatusCode::FORBIDDEN, "guests are not allowed"))?,
+    };
+    info!(
+        "{} logged in as {:?}",
+        identity.name.as_deref().unwrap_or_default(),
+        identity.role
+    );
+
+    let cookie =
+        format!("{TOKEN_COOKIE}={token}; Path=/; HttpOnly; SameSite=Strict; Max-Age=31536000");
+    Ok(([(header::SET_COOKIE, cookie)], Json(identity)).into_response())
+}
+
+async fn logout_handler() -> impl IntoResponse {
+    let cookie = format!("{TOKEN_COOKIE}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0");
+    ([(header::SET_COOKIE, cookie)], "logged out")
+}
+
+async fn me_handler(Extension(identity): Extension<Identity>) -> Json<Identity> {
+    Json(identity)
+}
+
+/// Keeps guests from having more than `guest_max_pending` jobs waiting at a time.
+async fn check_pending_limit(
+    state: &AppState,
+    identity: &Identity,
+    adding: usize,
+) -> Result<(), AppError> {
+    let Some(limit) = state.auth.guest_max_pending() else {
+        return Ok(());
+    };
+    if identity.role != Role::Guest {
+        return Ok(());
+    }
+    let (_, queue, _) = state.queue.inspect().await;
+    let pending = queue
+        .iter()
+        .filter(|job| job.submitter.is_some() && job.submitter == identity.name)
+        .count();
+    if pending + adding > limit {
+        return Err(AppError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("guests may have {limit} jobs waiting, {pending} are already queued"),
+        ));
+    }
+    Ok(())
+}
+
+fn require_owner(identity: &Identity, job: &InspectMetadata) -> Result<(), AppError> {
+    if identity.owns(job.submitter.as_deref()) {
+        Ok(())
+    } else {
+        Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            format!("job {} was queued by someone else", job.job_id),
+        ))
+    }
+}
+
+// Wrapper type for anyhow::Error
+#[derive(Debug)]
+struct AppError(StatusCode, anyhow::Error);
+
+impl AppError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        AppError(status, anyhow::anyhow!(message.into()))
+    }
+}
+
+// Implement From<anyhow::Error> to allow easy conversion
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        let status = match err.downcast_ref::<RequestError>() {
+            Some(RequestError::NotFound(_)) => StatusCode::NOT_FOUND,
+            Some(RequestError::Invalid(_)) => StatusCode::BAD_REQUEST,
+            None => match YtDlpError::code_of(&err) {
+                Some(ErrorCode::Unavailable) => StatusCode::NOT_FOUND,
+                Some(ErrorCode::Private | ErrorCode::SignInRequired) => StatusCode::FORBIDDEN,
+                Some(ErrorCode::GeoBlocked) => StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+                Some(ErrorCode::NoMatchingFormat) => StatusCode::UNPROCESSABLE_ENTITY,
+                Some(ErrorCode::UnsupportedUrl) => StatusCode::BAD_REQUEST,
+                Some(ErrorCode::Timeout) => StatusCode::GATEWAY_TIMEOUT,
+                Some(ErrorCode::Failed) => StatusCode::BAD_GATEWAY,
+                None => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+        };
+        AppError(status, err)
+    }
+}
+
+// Implement IntoResponse so Axum can convert your error into an HTTP response
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        if self.0.is_server_error() {
+            eprintln!("Internal error: {:?}", self.1); // Logging
+        }
+        (
+            self.0,
+            Json(serde_json::json!({
+                "error": self.1.to_string(),
+                // only set for yt-dlp errors
+                "code": YtDlpError::code_of(&self.1),
+            })),
+        )
+            .into_response()
+    }
+}
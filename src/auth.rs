@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rand::{Rng, distr::Alphanumeric};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, config::AuthConfig};
+
+pub const TOKEN_COOKIE: &str = "remote_yt_token";
+const ADMIN_NAME: &str = "admin";
+const GUEST_TOKEN_LEN: usize = 32;
+
+/// Resolves the tokens sent with requests to the caller.
+pub struct Auth {
+    enabled: bool,
+    guests: bool,
+    guest_max_pending: Option<usize>,
+    // token -> caller
+    tokens: HashMap<String, Identity>,
+    // handed out by `join_as_guest`, forgotten on restart
+    guest_tokens: Mutex<HashMap<String, Identity>>,
+    // name -> time of the last request
+    last_seen: Mutex<HashMap<String, Instant>>,
+}
+
+/// What a caller may do, every role can do everything the roles before it can.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// add to the queue and vote
+    Guest,
+    /// also reorder, cancel and retry their own jobs
+    #[default]
+    Member,
+    /// everything, including other people's jobs and the player
+    Admin,
+}
+
+/// Who is making a request, added to the request extensions by `require_identity`.
+#[derive(Serialize, Clone, Debug)]
+pub struct Identity {
+    /// `None` when auth is disabled
+    pub name: Option<String>,
+    pub role: Role,
+}
+
+impl Identity {
+    /// Used for every request when auth is disabled.
+    pub fn anonymous() -> Self {
+        Self {
+            name: None,
+            role: Role::Admin,
+        }
+    }
+
+    /// Whether the caller may act on something submitted by `submitter`.
+    pub fn owns(&self, submitter: Option<&str>) -> bool {
+        self.role == Role::Admin || (self.name.is_some() && self.name.as_deref() == submitter)
+    }
+}
+
+impl Auth {
+    pub fn new(config: &AuthConfig) -> Self {
+        let mut tokens = HashMap::new();
+        if let Some(pin) = &config.admin_pin {
+            tokens.insert(
+                pin.clone(),
+                Identity {
+                    name: Some(ADMIN_NAME.into()),
+                    role: Role::Admin,
+                },
+            );
+        }
+        for user in &config.users {
+            tokens.insert(
+                user.token.clone(),
+                Identity {
+                    name: Some(user.name.clone()),
+                    role: user.role,
+                },
+            );
+        }
+        Self {
+            enabled: config.enabled,
+            guests: config.guests,
+            guest_max_pending: config.guest_max_pending,
+            tokens,
+            guest_tokens: Mutex::new(HashMap::new()),
+            last_seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// How many jobs a guest may have waiting in the queue, `None` for no limit.
+    pub fn guest_max_pending(&self) -> Option<usize> {
+        self.guest_max_pending
+    }
+
+    pub fn identify(&self, token: &str) -> Option<Identity> {
+        if let Some(identity) = self.tokens.get(token) {
+            return Some(identity.clone());
+        }
+        self.guest_tokens.lock().unwrap().get(token).cloned()
+    }
+
+    /// Hands out a token for a guest that picked `name`, returns `None` if guests are
+    /// not allowed.
+    pub fn join_as_guest(&self, name: &str) -> anyhow::Result<Option<(String, Identity)>> {
+        if !self.guests {
+            return Ok(None);
+        }
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow::anyhow!("guests need a name"));
+        }
+
+        let mut guest_tokens = self.guest_tokens.lock().unwrap();
+        let taken = name == ADMIN_NAME
+            || self
+                .tokens
+                .values()
+                .chain(guest_tokens.values())
+                .any(|identity| identity.name.as_deref() == Some(name));
+        if taken {
+            return Err(anyhow::anyhow!("the name {name} is already taken"));
+        }
+
+        let token: String = rand::rng()
+            .sample_iter(Alphanumeric)
+            .take(GUEST_TOKEN_LEN)
+            .map(char::from)
+            .collect();
+        let identity = Identity {
+            name: Some(name.to_string()),
+            role: Role::Guest,
+        };
+        guest_tokens.insert(token.clone(), identity.clone());
+        Ok(Some((token, identity)))
+    }
+
+    /// How many named callers made a request within `window`.
+    pub fn active_users(&self, window: Duration) -> usize {
+        let last_seen = self.last_seen.lock().unwrap();
+        last_seen
+            .values()
+            .filter(|seen| seen.elapsed() <= window)
+            .count()
+    }
+
+    fn seen(&self, identity: &Identity) {
+        if let Some(name) = &identity.name {
+            let mut last_seen = self.last_seen.lock().unwrap();
+            last_seen.insert(name.clone(), Instant::now());
+        }
+    }
+
+    /// Reads the token from the `Authorization: Bearer` header or the login cookie.
+    fn identify_request(&self, headers: &HeaderMap) -> Option<Identity> {
+        if !self.enabled {
+            return Some(Identity::anonymous());
+        }
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let cookie = headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(name, _)| *name == TOKEN_COOKIE)
+            .map(|(_, token)| token);
+        bearer.or(cookie).and_then(|token| self.identify(token))
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// Rejects requests without a valid token and hands the caller to the handlers.
+pub async fn require_identity(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match state.auth.identify_request(request.headers()) {
+        Some(identity) => {
+            state.auth.seen(&identity);
+            request.extensions_mut().insert(identity);
+            next.run(request).await
+        }
+        None => error_response(StatusCode::UNAUTHORIZED, "missing or invalid token"),
+    }
+}
+
+async fn require_role(role: Role, request: Request, next: Next) -> Response {
+    match request.extensions().get::<Identity>() {
+        Some(identity) if identity.role >= role => next.run(request).await,
+        Some(_) => error_response(
+            StatusCode::FORBIDDEN,
+            &format!("this needs the {role:?} role").to_lowercase(),
+        ),
+        None => error_response(StatusCode::UNAUTHORIZED, "missing or invalid token"),
+    }
+}
+
+/// Only lets members and admins through, must run after `require_identity`.
+pub async fn require_member(request: Request, next: Next) -> Response {
+    require_role(Role::Member, request, next).await
+}
+
+/// Only lets admins through, must run after `require_identity`.
+pub async fn require_admin(request: Request, next: Next) -> Response {
+    require_role(Role::Admin, request, next).await
+}
@@ -0,0 +1,233 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use tracing::{info, warn};
+
+use crate::{config::CacheConfig, format::MinHeight, job::unix_timestamp};
+
+/// Downloaded files of `QueueFile` jobs, kept for when the same video is queued again and
+/// evicted least recently used first once they take up more than `max_bytes`.
+pub struct DownloadCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    // file name -> entry
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    // file -> lock held by the job downloading it
+    downloading: std::sync::Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CacheEntry {
+    pub name: String,
+    pub size: u64,
+    /// unix timestamp of the last download or reuse, kept as the file's mtime across restarts
+    pub last_used: u64,
+}
+
+impl DownloadCache {
+    /// Creates the cache directory if needed and picks up the files already in it.
+    pub async fn new(config: &CacheConfig) -> anyhow::Result<Self> {
+        tokio::fs::create_dir_all(&config.dir).await.map_err(|e| {
+            anyhow::anyhow!("failed to create cache dir {}: {e}", config.dir.display())
+        })?;
+
+        let mut entries = HashMap::new();
+        let mut dir = tokio::fs::read_dir(&config.dir).await?;
+        while let Some(file) = dir.next_entry().await? {
+            let metadata = file.metadata().await?;
+            let Some(name) = file.file_name().to_str().map(String::from) else {
+                continue;
+            };
+            // finished downloads have no extension, anything else was left behind by an
+            // interrupted one, e.g. `.part` fragments
+            if !metadata.is_file() || name.contains('.') {
+                continue;
+            }
+            let last_used = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_secs())
+                .unwrap_or_default();
+            entries.insert(
+                name.clone(),
+                CacheEntry {
+                    name,
+                    size: metadata.len(),
+                    last_used,
+                },
+            );
+        }
+        if !entries.is_empty() {
+            info!("found {} cached downloads", entries.len());
+        }
+
+        Ok(Self {
+            dir: config.dir.clone(),
+            max_bytes: config.max_bytes,
+            entries: Mutex::new(entries),
+            downloading: std::sync::Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Where the download of a video in a given height is kept.
+    pub fn path(&self, video_id: &str, height: MinHeight) -> PathBuf {
+        let video_id: String = video_id
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect();
+        self.dir.join(format!("{video_id}-{}p", height.0))
+    }
+
+    fn name(&self, file: &Path) -> Option<String> {
+        if file.parent() != Some(self.dir.as_path()) {
+            return None;
+        }
+        file.file_name()?.to_str().map(String::from)
+    }
+
+    fn download_lock(&self, file: &Path) -> Arc<Mutex<()>> {
+        let mut downloading = self.downloading.lock().unwrap();
+        // locks nobody holds or waits for are no longer needed
+        downloading.retain(|_, lock| Arc::strong_count(lock) > 1);
+        downloading.entry(file.to_path_buf()).or_default().clone()
+    }
+
+    /// Claims the download of `file`, waiting while another job downloads it. The file
+    /// may have been cached by then, so check with `touch` once this returns.
+    pub async fn claim_download(&self, file: &Path) -> OwnedMutexGuard<()> {
+        self.download_lock(file).lock_owned().await
+    }
+
+    /// Like `claim_download`, but returns `None` instead of waiting.
+    pub fn try_claim_download(&self, file: &Path) -> Option<OwnedMutexGuard<()>> {
+        self.download_lock(file).try_lock_owned().ok()
+    }
+
+    /// Marks a cached file as used, returns false if it is not cached.
+    pub async fn touch(&self, file: &Path) -> bool {
+        let Some(name) = self.name(file) else {
+            return false;
+        };
+        if !self.entries.lock().await.contains_key(&name) {
+            return false;
+        }
+        if !tokio::fs::try_exists(file).await.unwrap_or(false) {
+            self.entries.lock().await.remove(&name);
+            return false;
+        }
+        if let Some(entry) = self.entries.lock().await.get_mut(&name) {
+            entry.last_used = unix_timestamp();
+        }
+
+        let path = file.to_path_buf();
+        let touched = tokio::task::spawn_blocking(move || {
+            std::fs::File::options()
+                .write(true)
+                .open(path)?
+                .set_modified(SystemTime::now())
+        })
+        .await
+        .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+        if let Err(e) = touched {
+            warn!("failed to touch {}: {e}", file.display());
+        }
+        true
+    }
+
+    /// Adds a finished download.
+    pub async fn insert(&self, file: &Path) -> anyhow::Result<()> {
+        let name = self
+            .name(file)
+            .ok_or_else(|| anyhow::anyhow!("{} is not in the cache dir", file.display()))?;
+        let size = tokio::fs::metadata(file).await?.len();
+        self.entries.lock().await.insert(
+            name.clone(),
+            CacheEntry {
+                name,
+                size,
+                last_used: unix_timestamp(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Deletes a cached file, returns false if it was not cached.
+    pub async fn remove(&self, file: &Path) -> bool {
+        let Some(name) = self.name(file) else {
+            return false;
+        };
+        let removed = self.entries.lock().await.remove(&name).is_some();
+        if removed {
+            info!("removing cached download {name}");
+            if let Err(e) = tokio::fs::remove_file(file).await {
+                warn!("failed to remove {}: {e}", file.display());
+            }
+        }
+        removed
+    }
+
+    /// Deletes the least recently used files that are not `in_use` until the cache fits
+    /// into `max_bytes` again.
+    pub async fn evict(&self, in_use: &HashSet<PathBuf>) {
+        let mut candidates: Vec<_> = {
+            let entries = self.entries.lock().await;
+            let total: u64 = entries.values().map(|entry| entry.size).sum();
+            if total <= self.max_bytes {
+                return;
+            }
+            let mut excess = total - self.max_bytes;
+            let mut candidates: Vec<_> = entries
+                .values()
+                .filter(|entry| !in_use.contains(&self.dir.join(&entry.name)))
+                .cloned()
+                .collect();
+            candidates.sort_by_key(|entry| entry.last_used);
+            candidates.retain(|entry| {
+                let keep = excess > 0;
+                excess = excess.saturating_sub(entry.size);
+                keep
+            });
+            candidates
+        };
+        for entry in candidates.drain(..) {
+            self.remove(&self.dir.join(&entry.name)).await;
+        }
+    }
+
+    /// Every cached file, most recently used first.
+    pub async fn entries(&self) -> Vec<CacheEntry> {
+        let mut entries: Vec<_> = self.entries.lock().await.values().cloned().collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_used));
+        entries
+    }
+
+    /// The path of the cached file with the given name, `None` if the name points elsewhere.
+    pub fn file(&self, name: &str) -> Option<PathBuf> {
+        let file = self.dir.join(name);
+        self.name(&file).is_some().then_some(file)
+    }
+
+    /// Deletes every file that is not `in_use`, returns how many were deleted.
+    pub async fn purge(&self, in_use: &HashSet<PathBuf>) -> usize {
+        let names: Vec<_> = self.entries.lock().await.keys().cloned().collect();
+        let mut purged = 0;
+        for name in names {
+            let file = self.dir.join(name);
+            if !in_use.contains(&file) && self.remove(&file).await {
+                purged += 1;
+            }
+        }
+        purged
+    }
+}
@@ -0,0 +1,662 @@
+use std::{collections::HashSet, net::SocketAddr, path::PathBuf, time::Duration};
+
+use clap::Parser;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{auth::Role, format::MinHeight};
+
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// path to a TOML config file
+    #[arg(short, long, env = "REMOTE_YT_CONFIG")]
+    config: Option<PathBuf>,
+    /// address the web server listens on
+    #[arg(long, env = "REMOTE_YT_LISTEN")]
+    listen: Option<SocketAddr>,
+    /// directory containing the built UI
+    #[arg(long, env = "REMOTE_YT_STATIC_DIR")]
+    static_dir: Option<PathBuf>,
+    /// file the playback history is stored in
+    #[arg(long, env = "REMOTE_YT_HISTORY_FILE")]
+    history_file: Option<PathBuf>,
+    /// file the pending queue is journaled to
+    #[arg(long, env = "REMOTE_YT_QUEUE_FILE")]
+    queue_file: Option<PathBuf>,
+    /// whether the job that was playing during a restart is queued again
+    #[arg(long, env = "REMOTE_YT_RESUME_INTERRUPTED")]
+    resume_interrupted: Option<bool>,
+    /// order in which queued jobs are played
+    #[arg(long, env = "REMOTE_YT_SCHEDULING")]
+    scheduling: Option<Scheduling>,
+    /// how many upcoming jobs are kept ready to play
+    #[arg(long, env = "REMOTE_YT_PREFETCH")]
+    prefetch: Option<usize>,
+    /// height used when a request does not specify one
+    #[arg(long, env = "REMOTE_YT_DEFAULT_HEIGHT")]
+    default_height: Option<u32>,
+    /// path to the yt-dlp binary
+    #[arg(long, env = "REMOTE_YT_YT_DLP_BINARY")]
+    yt_dlp_binary: Option<PathBuf>,
+    /// directory downloaded files are kept in
+    #[arg(long, env = "REMOTE_YT_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+    /// media player used for playback
+    #[arg(long, env = "REMOTE_YT_PLAYER")]
+    player: Option<PlayerBackend>,
+    /// whether vlc is started per job or kept running
+    #[arg(long, env = "REMOTE_YT_VLC_MODE")]
+    vlc_mode: Option<VlcMode>,
+    /// path to the vlc binary
+    #[arg(long, env = "REMOTE_YT_VLC_BINARY")]
+    vlc_binary: Option<PathBuf>,
+    /// host the vlc http interface binds to
+    #[arg(long, env = "REMOTE_YT_VLC_HOST")]
+    vlc_host: Option<String>,
+    /// port of the vlc http interface
+    #[arg(long, env = "REMOTE_YT_VLC_PORT")]
+    vlc_port: Option<u16>,
+    /// password of the vlc http interface
+    #[arg(long, env = "REMOTE_YT_VLC_PASSWORD")]
+    vlc_password: Option<String>,
+    /// path to the mpv binary
+    #[arg(long, env = "REMOTE_YT_MPV_BINARY")]
+    mpv_binary: Option<PathBuf>,
+    /// path of the mpv JSON IPC socket
+    #[arg(long, env = "REMOTE_YT_MPV_SOCKET")]
+    mpv_socket: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listen: SocketAddr,
+    pub static_dir: PathBuf,
+    pub history_file: PathBuf,
+    pub queue_file: PathBuf,
+    pub resume_interrupted: bool,
+    pub scheduling: Scheduling,
+    pub prefetch: usize,
+    pub default_height: u32,
+    pub player: PlayerBackend,
+    pub yt_dlp: YtDlpConfig,
+    pub vlc: VlcConfig,
+    pub mpv: MpvConfig,
+    pub retry: RetryConfig,
+    pub auth: AuthConfig,
+    pub votes: VotesConfig,
+    pub search: SearchConfig,
+    pub cache: CacheConfig,
+    pub resume: ResumeConfig,
+    pub subtitles: SubtitlesConfig,
+    pub sponsorblock: SponsorBlockConfig,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheduling {
+    /// play jobs in the order they were queued
+    #[default]
+    Fifo,
+    /// take turns between the people who queued jobs; a new job is slotted in behind
+    /// the turns its submitter already has queued, the queue is not reordered after
+    /// that, so votes, moves and a restored queue keep their order. Without auth every
+    /// job has the same (no) submitter, which makes this the same as fifo. Only admins
+    /// may move jobs
+    Fair,
+    /// play the jobs with the most votes first, only admins may move jobs
+    Democratic,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayerBackend {
+    /// vlc controlled through its http interface
+    #[default]
+    Vlc,
+    /// mpv controlled through its JSON IPC socket
+    Mpv,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct YtDlpConfig {
+    pub binary: PathBuf,
+    /// how long looking up a video, playlist or search may take before yt-dlp is killed;
+    /// playlists and searches also wait at most this long for a free slot
+    pub timeout_secs: u64,
+    /// how long a download may take before yt-dlp is killed
+    pub download_timeout_secs: u64,
+    /// how many yt-dlp processes may run at once in the background, the rest wait for a
+    /// free slot; looking up the track that is about to play has a slot of its own
+    pub max_processes: usize,
+    /// how many entries of a playlist or channel are queued at most, counted from the
+    /// first one asked for
+    pub max_playlist_entries: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct VlcConfig {
+    pub mode: VlcMode,
+    pub binary: PathBuf,
+    pub host: String,
+    pub port: u16,
+    pub password: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum VlcMode {
+    /// start a new vlc for every job
+    #[default]
+    Oneshot,
+    /// keep a single vlc running and load jobs into its playlist
+    Persistent,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MpvConfig {
+    pub binary: PathBuf,
+    pub socket: PathBuf,
+    /// passed to mpv's `--hwdec`
+    pub hwdec: String,
+    /// let mpv's ytdl hook resolve streams instead of handing it resolved URLs
+    pub ytdl_hook: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// require a token for every API request
+    pub enabled: bool,
+    /// shared PIN that identifies the caller as admin
+    pub admin_pin: Option<String>,
+    pub users: Vec<UserConfig>,
+    /// let people without a token join as guests under a name of their choice
+    pub guests: bool,
+    /// how many jobs a guest may have waiting in the queue
+    pub guest_max_pending: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    pub name: String,
+    pub token: String,
+    #[serde(default)]
+    pub role: Role,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct VotesConfig {
+    /// share of the active users that has to vote to skip the current job
+    pub skip_ratio: f64,
+    /// users who made a request within this many seconds count as active
+    pub active_window_secs: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// upper bound for the `limit` of a search request
+    pub max_results: usize,
+    /// how long the results of a query are reused
+    pub cache_secs: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// directory the downloads of `queue_file` are kept in
+    pub dir: PathBuf,
+    /// the least recently used downloads are deleted once they take up more than this
+    pub max_bytes: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SubtitlesConfig {
+    /// languages to show subtitles in, the first one a video has is used
+    pub languages: Vec<String>,
+    /// whether automatic captions are used when a video has no subtitles in a language
+    pub auto_captions: bool,
+    /// where subtitles are downloaded to before playback
+    pub dir: PathBuf,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SponsorBlockConfig {
+    /// whether skip segments are looked up when a video is resolved
+    pub enabled: bool,
+    /// SponsorBlock API yt-dlp asks for the segments, `None` for yt-dlp's default
+    pub api_url: Option<String>,
+    /// categories of segments that are skipped during playback
+    pub skip: Vec<String>,
+}
+
+/// Categories of SponsorBlock segments that have a start and an end.
+pub const SPONSORBLOCK_CATEGORIES: &[&str] = &[
+    "sponsor",
+    "intro",
+    "outro",
+    "selfpromo",
+    "preview",
+    "filler",
+    "interaction",
+    "music_offtopic",
+];
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResumeConfig {
+    /// how often the position of the playing video is saved to the history
+    pub save_interval_secs: u64,
+    /// videos stopped before this many seconds start over
+    pub min_position_secs: u32,
+    /// videos stopped less than this many seconds before the end start over
+    pub end_margin_secs: u32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// how often a job is started before it is marked as failed
+    pub max_attempts: u32,
+    /// delay before the second attempt, doubled for every further attempt
+    pub backoff_secs: u64,
+    /// playback that stops more than this many seconds before the end counts as a failure
+    pub early_exit_margin_secs: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from(([0, 0, 0, 0], 8080)),
+            static_dir: "ui/dist".into(),
+            history_file: "history.json".into(),
+            queue_file: "queue.json".into(),
+            resume_interrupted: true,
+            scheduling: Scheduling::default(),
+            prefetch: 2,
+            default_height: 480,
+            player: PlayerBackend::default(),
+            yt_dlp: YtDlpConfig::default(),
+            vlc: VlcConfig::default(),
+            mpv: MpvConfig::default(),
+            retry: RetryConfig::default(),
+            auth: AuthConfig::default(),
+            votes: VotesConfig::default(),
+            search: SearchConfig::default(),
+            cache: CacheConfig::default(),
+            resume: ResumeConfig::default(),
+            subtitles: SubtitlesConfig::default(),
+            sponsorblock: SponsorBlockConfig::default(),
+        }
+    }
+}
+
+impl Default for YtDlpConfig {
+    fn default() -> Self {
+        Self {
+            binary: "yt-dlp".into(),
+            timeout_secs: 60,
+            download_timeout_secs: 3600,
+            max_processes: 2,
+            max_playlist_entries: 100,
+        }
+    }
+}
+
+impl YtDlpConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    pub fn download_timeout(&self) -> Duration {
+        Duration::from_secs(self.download_timeout_secs)
+    }
+}
+
+impl Default for VlcConfig {
+    fn default() -> Self {
+        let binary = if cfg!(target_os = "macos") {
+            "/Applications/VLC.app/Contents/MacOS/VLC".into()
+        } else {
+            "vlc".into()
+        };
+        Self {
+            mode: VlcMode::default(),
+            binary,
+            host: "0.0.0.0".into(),
+            port: 8081,
+            password: "abc".into(),
+        }
+    }
+}
+
+impl Default for MpvConfig {
+    fn default() -> Self {
+        Self {
+            binary: "mpv".into(),
+            socket: std::env::temp_dir().join("remote-yt-mpv.sock"),
+            hwdec: "auto".into(),
+            ytdl_hook: true,
+        }
+    }
+}
+
+impl Default for VotesConfig {
+    fn default() -> Self {
+        Self {
+            skip_ratio: 0.5,
+            active_window_secs: 900,
+        }
+    }
+}
+
+impl VotesConfig {
+    pub fn active_window(&self) -> Duration {
+        Duration::from_secs(self.active_window_secs)
+    }
+
+    /// How many skip votes it takes when `active` users are around, at least one.
+    pub fn skip_votes_needed(&self, active: usize) -> usize {
+        ((active as f64 * self.skip_ratio).ceil() as usize).max(1)
+    }
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            max_results: 20,
+            cache_secs: 600,
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            dir: "cache".into(),
+            max_bytes: 10 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for SubtitlesConfig {
+    fn default() -> Self {
+        Self {
+            languages: vec![],
+            auto_captions: true,
+            dir: std::env::temp_dir().join("remote-yt-subtitles"),
+        }
+    }
+}
+
+impl Default for SponsorBlockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            api_url: None,
+            skip: vec!["sponsor".into(), "intro".into(), "selfpromo".into()],
+        }
+    }
+}
+
+impl SponsorBlockConfig {
+    /// Whether segments of `category` are skipped.
+    pub fn skips(&self, category: &str) -> bool {
+        self.enabled && self.skip.iter().any(|skip| skip == category)
+    }
+}
+
+impl Default for ResumeConfig {
+    fn default() -> Self {
+        Self {
+            save_interval_secs: 15,
+            min_position_secs: 30,
+            end_margin_secs: 60,
+        }
+    }
+}
+
+impl ResumeConfig {
+    pub fn save_interval(&self) -> Duration {
+        Duration::from_secs(self.save_interval_secs)
+    }
+
+    /// Where a video of `duration` seconds that was stopped at `position` resumes from,
+    /// `None` if it starts over.
+    pub fn resume_at(&self, position: u32, duration: u32) -> Option<u32> {
+        let nearly_finished = duration > 0 && position + self.end_margin_secs >= duration;
+        (position >= self.min_position_secs && !nearly_finished).then_some(position)
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff_secs: 2,
+            early_exit_margin_secs: 30,
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        Duration::from_secs(self.backoff_secs.saturating_mul(1 << (attempt - 1).min(16)))
+    }
+}
+
+impl Config {
+    /// Reads the config file (if any), applies CLI/env overrides and validates the result.
+    pub fn load(args: Args) -> anyhow::Result<Self> {
+        let mut config = match &args.config {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path).map_err(|e| {
+                    anyhow::anyhow!("failed to read config file {}: {e}", path.display())
+                })?;
+                toml::from_str::<Config>(&contents).map_err(|e| {
+                    anyhow::anyhow!("failed to parse config file {}: {e}", path.display())
+                })?
+            }
+            None => Config::default(),
+        };
+
+        if let Some(listen) = args.listen {
+            config.listen = listen;
+        }
+        if let Some(static_dir) = args.static_dir {
+            config.static_dir = static_dir;
+        }
+        if let Some(history_file) = args.history_file {
+            config.history_file = history_file;
+        }
+        if let Some(queue_file) = args.queue_file {
+            config.queue_file = queue_file;
+        }
+        if let Some(resume_interrupted) = args.resume_interrupted {
+            config.resume_interrupted = resume_interrupted;
+        }
+        if let Some(scheduling) = args.scheduling {
+            config.scheduling = scheduling;
+        }
+        if let Some(prefetch) = args.prefetch {
+            config.prefetch = prefetch;
+        }
+        if let Some(default_height) = args.default_height {
+            config.default_height = default_height;
+        }
+        if let Some(binary) = args.yt_dlp_binary {
+            config.yt_dlp.binary = binary;
+        }
+        if let Some(dir) = args.cache_dir {
+            config.cache.dir = dir;
+        }
+        if let Some(player) = args.player {
+            config.player = player;
+        }
+        if let Some(mode) = args.vlc_mode {
+            config.vlc.mode = mode;
+        }
+        if let Some(binary) = args.vlc_binary {
+            config.vlc.binary = binary;
+        }
+        if let Some(host) = args.vlc_host {
+            config.vlc.host = host;
+        }
+        if let Some(port) = args.vlc_port {
+            config.vlc.port = port;
+        }
+        if let Some(password) = args.vlc_password {
+            config.vlc.password = password;
+        }
+        if let Some(binary) = args.mpv_binary {
+            config.mpv.binary = binary;
+        }
+        if let Some(socket) = args.mpv_socket {
+            config.mpv.socket = socket;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.default_height == 0 {
+            return Err(anyhow::anyhow!("default_height must be greater than 0"));
+        }
+        match self.player {
+            PlayerBackend::Vlc => self.validate_vlc()?,
+            PlayerBackend::Mpv => self.validate_mpv()?,
+        }
+        if self.retry.max_attempts == 0 {
+            return Err(anyhow::anyhow!("retry.max_attempts must be at least 1"));
+        }
+        if self.auth.enabled {
+            self.validate_auth()?;
+        }
+        if !(self.votes.skip_ratio > 0.0 && self.votes.skip_ratio <= 1.0) {
+            return Err(anyhow::anyhow!(
+                "votes.skip_ratio must be greater than 0 and at most 1"
+            ));
+        }
+        if self.resume.save_interval_secs == 0 {
+            return Err(anyhow::anyhow!(
+                "resume.save_interval_secs must be greater than 0"
+            ));
+        }
+        if let Some(category) = self
+            .sponsorblock
+            .skip
+            .iter()
+            .find(|category| !SPONSORBLOCK_CATEGORIES.contains(&category.as_str()))
+        {
+            return Err(anyhow::anyhow!(
+                "sponsorblock.skip has unknown category {category}, expected one of {}",
+                SPONSORBLOCK_CATEGORIES.join(", ")
+            ));
+        }
+        if self.search.max_results == 0 {
+            return Err(anyhow::anyhow!("search.max_results must be at least 1"));
+        }
+        if self.queue_file == self.history_file {
+            return Err(anyhow::anyhow!(
+                "queue_file and history_file must differ, both are {}",
+                self.queue_file.display()
+            ));
+        }
+        if self.yt_dlp.binary.as_os_str().is_empty() {
+            return Err(anyhow::anyhow!("yt_dlp.binary must not be empty"));
+        }
+        if self.yt_dlp.timeout_secs == 0 || self.yt_dlp.download_timeout_secs == 0 {
+            return Err(anyhow::anyhow!(
+                "yt_dlp.timeout_secs and yt_dlp.download_timeout_secs must be greater than 0"
+            ));
+        }
+        if self.yt_dlp.max_processes == 0 {
+            return Err(anyhow::anyhow!("yt_dlp.max_processes must be at least 1"));
+        }
+        if self.yt_dlp.max_playlist_entries == 0 {
+            return Err(anyhow::anyhow!(
+                "yt_dlp.max_playlist_entries must be at least 1"
+            ));
+        }
+        if !self.static_dir.is_dir() {
+            warn!(
+                "static_dir {} does not exist, the UI will not be served",
+                self.static_dir.display()
+            );
+        }
+        Ok(())
+    }
+
+    fn validate_vlc(&self) -> anyhow::Result<()> {
+        if self.vlc.port == 0 {
+            return Err(anyhow::anyhow!("vlc.port must not be 0"));
+        }
+        if self.vlc.port == self.listen.port() {
+            return Err(anyhow::anyhow!(
+                "vlc.port and listen port must differ, both are {}",
+                self.vlc.port
+            ));
+        }
+        if self.vlc.host.is_empty() {
+            return Err(anyhow::anyhow!("vlc.host must not be empty"));
+        }
+        // vlc refuses to start its http interface without a password
+        if self.vlc.password.is_empty() {
+            return Err(anyhow::anyhow!("vlc.password must not be empty"));
+        }
+        if self.vlc.binary.as_os_str().is_empty() {
+            return Err(anyhow::anyhow!("vlc.binary must not be empty"));
+        }
+        Ok(())
+    }
+
+    fn validate_mpv(&self) -> anyhow::Result<()> {
+        if self.mpv.binary.as_os_str().is_empty() {
+            return Err(anyhow::anyhow!("mpv.binary must not be empty"));
+        }
+        if self.mpv.socket.as_os_str().is_empty() {
+            return Err(anyhow::anyhow!("mpv.socket must not be empty"));
+        }
+        Ok(())
+    }
+
+    fn validate_auth(&self) -> anyhow::Result<()> {
+        let admin_pin = self.auth.admin_pin.as_deref().unwrap_or_default();
+        if admin_pin.is_empty() {
+            return Err(anyhow::anyhow!(
+                "auth.admin_pin must be set when auth is enabled"
+            ));
+        }
+        let mut names = HashSet::new();
+        let mut tokens = HashSet::from([admin_pin]);
+        for user in &self.auth.users {
+            if user.name.is_empty() || user.token.is_empty() {
+                return Err(anyhow::anyhow!("auth.users need a name and a token"));
+            }
+            if !names.insert(user.name.as_str()) {
+                return Err(anyhow::anyhow!("auth user {} is listed twice", user.name));
+            }
+            if !tokens.insert(user.token.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "the token of auth user {} is already in use",
+                    user.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn default_height(&self) -> MinHeight {
+        MinHeight(self.default_height)
+    }
+}
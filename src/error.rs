@@ -0,0 +1,31 @@
+use std::fmt;
+
+/// A request that cannot be carried out as asked, answered with a client error by the API
+/// instead of a 500.
+#[derive(Debug)]
+pub enum RequestError {
+    /// the job, chapter or playlist entries asked for do not exist
+    NotFound(String),
+    /// anything else that is wrong with the request, e.g. swapping a job with itself
+    Invalid(String),
+}
+
+impl RequestError {
+    pub fn not_found(message: impl Into<String>) -> anyhow::Error {
+        Self::NotFound(message.into()).into()
+    }
+
+    pub fn invalid(message: impl Into<String>) -> anyhow::Error {
+        Self::Invalid(message.into()).into()
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(message) | Self::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
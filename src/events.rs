@@ -0,0 +1,86 @@
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::{meta::InspectMetadata, player::PlayerStatus, yt_dlp::Segment};
+
+const EVENT_CAPACITY: usize = 64;
+
+#[derive(Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    JobAdded {
+        job: InspectMetadata,
+    },
+    /// a queued job changed its status, e.g. from resolving to ready
+    JobUpdated {
+        job: InspectMetadata,
+    },
+    /// job ids of the queue in their new order
+    QueueReordered {
+        queue: Vec<usize>,
+    },
+    JobStarted {
+        job: InspectMetadata,
+    },
+    /// the job completed or was cancelled
+    JobFinished {
+        job: InspectMetadata,
+    },
+    JobFailed {
+        job: InspectMetadata,
+    },
+    QueueCleared,
+    HistoryChanged,
+    PlayerState {
+        player: Option<PlayerStatus>,
+    },
+    /// playback of the job jumped past a SponsorBlock segment
+    SegmentSkipped {
+        job_id: usize,
+        segment: Segment,
+    },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::JobAdded { .. } => "job_added",
+            Event::JobUpdated { .. } => "job_updated",
+            Event::QueueReordered { .. } => "queue_reordered",
+            Event::JobStarted { .. } => "job_started",
+            Event::JobFinished { .. } => "job_finished",
+            Event::JobFailed { .. } => "job_failed",
+            Event::QueueCleared => "queue_cleared",
+            Event::HistoryChanged => "history_changed",
+            Event::PlayerState { .. } => "player_state",
+            Event::SegmentSkipped { .. } => "segment_skipped",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Events {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl Events {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&self, event: Event) {
+        // nobody listening is not an error
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
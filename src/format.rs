@@ -1,4 +1,7 @@
-#[derive(Clone, Copy)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct MinHeight(pub u32);
 
 impl Default for MinHeight {
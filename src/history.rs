@@ -1,12 +1,12 @@
-use std::{
-    path::PathBuf,
-    time::{SystemTime, UNIX_EPOCH},
-};
+use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
-use tokio::fs::{read_to_string, write};
+use tokio::fs::{read_to_string, rename, write};
 
-use crate::yt_dlp::TrackInfo;
+use crate::{
+    job::unix_timestamp,
+    yt_dlp::{TrackInfo, Video},
+};
 
 pub struct History {
     history_file: PathBuf,
@@ -16,6 +16,11 @@ pub struct History {
 #[derive(Serialize, Deserialize, Clone)]
 struct ExtraInfo {
     inserted_at: u64,
+    #[serde(default)]
+    submitter: Option<String>,
+    /// seconds into the video playback can resume from, `None` to start over
+    #[serde(default)]
+    position: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -46,16 +51,31 @@ impl History {
     pub fn get_history(&self) -> Vec<HistoryEntry> {
         self.contents.clone()
     }
+    /// Whether the latest entry already is `webpage_url` with playback at `position`.
+    pub fn is_saved(&self, webpage_url: &str, position: Option<u32>) -> bool {
+        self.contents.last().is_some_and(|content| {
+            content.track_info.webpage_url == webpage_url && content.extra_info.position == position
+        })
+    }
     async fn flush(&self) -> anyhow::Result<()> {
-        write(&self.history_file, serde_json::to_string(&self.contents)?).await?;
+        // positions are saved while playing, so never leave a truncated file behind
+        let mut temp_file = self.history_file.clone().into_os_string();
+        temp_file.push(".tmp");
+        write(&temp_file, serde_json::to_string(&self.contents)?).await?;
+        rename(&temp_file, &self.history_file).await?;
         Ok(())
     }
-    pub async fn insert(&mut self, track_info: TrackInfo) -> anyhow::Result<()> {
-        let inserted_at = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        let extra_info = ExtraInfo { inserted_at };
+    pub async fn insert(
+        &mut self,
+        track_info: TrackInfo,
+        submitter: Option<String>,
+        position: Option<u32>,
+    ) -> anyhow::Result<()> {
+        let extra_info = ExtraInfo {
+            inserted_at: unix_timestamp(),
+            submitter,
+            position,
+        };
         let has_entry = self
             .contents
             .iter()
@@ -82,6 +102,19 @@ impl History {
         self.flush().await?;
         Ok(())
     }
+    /// Where playback of the video behind `link` can resume from, matched by the
+    /// video id if `link` is not the exact `webpage_url`.
+    pub fn position(&self, link: &str) -> Option<u32> {
+        let video_id = Video::video_id(link);
+        self.contents
+            .iter()
+            .rev()
+            .find(|content| {
+                content.track_info.webpage_url == link
+                    || video_id.as_deref() == Some(content.track_info.id.as_str())
+            })
+            .and_then(|content| content.extra_info.position)
+    }
     pub async fn remove(&mut self, webpage_url: &str) -> anyhow::Result<()> {
         let index = self
             .contents
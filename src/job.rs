@@ -1,50 +1,517 @@
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use tokio::process::Child;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
 use tracing::{info, warn};
 
 use crate::{
-    format::MinHeight,
-    vlc::VlcClient,
-    yt_dlp::{Track, TrackInfo, Video},
+    config::SubtitlesConfig,
+    format::{Format, MinHeight},
+    player::{Playback, Player},
+    yt_dlp::{
+        DownloadProgress, ErrorCode, MergedTrack, Slot, SplitTrack, SubtitleInfo, Track, TrackInfo,
+        Video,
+    },
 };
 
+// streams whose URLs carry no expiry are reused for this long
+const DEFAULT_STREAM_LIFETIME_SECS: u64 = 600;
+// streams have to stay valid this long past the expected end of playback
+const EXPIRY_MARGIN_SECS: u64 = 300;
+
 #[allow(clippy::enum_variant_names)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum JobType {
     QueueMerged {
         url: String,
-        height: Option<u32>,
+        height: MinHeight,
         format_id: String,
     },
     QueueSplit {
         url: String,
-        height: Option<u32>,
+        height: MinHeight,
         format_id: String,
     },
     QueueFile {
+        #[serde(default)]
+        url: String,
+        #[serde(default)]
+        height: MinHeight,
         title: String,
         file: PathBuf,
     },
 }
 
-#[derive(Clone, Debug)]
+impl JobType {
+    pub fn url(&self) -> &str {
+        match self {
+            JobType::QueueMerged { url, .. }
+            | JobType::QueueSplit { url, .. }
+            | JobType::QueueFile { url, .. } => url,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum JobStatus {
+    /// every yt-dlp slot is taken, resolving or downloading starts once one is free
+    Waiting,
+    /// yt-dlp is looking up the track info
+    Resolving,
+    /// the file of a `QueueFile` job is being downloaded
+    Downloading,
+    /// queued and waiting for its turn
+    #[default]
+    Ready,
+    Playing,
+    Completed,
+    Cancelled,
+    Failed {
+        reason: String,
+        /// set when yt-dlp failed
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        code: Option<ErrorCode>,
+    },
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Cancelled | JobStatus::Failed { .. }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Vote {
+    Up,
+    Down,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Job {
     pub id: usize,
+    #[serde(default)]
+    pub batch_id: Option<usize>,
+    #[serde(default)]
+    pub status: JobStatus,
+    #[serde(default)]
+    pub queued_at: u64,
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    /// name of the user who queued the job, if auth is enabled
+    #[serde(default)]
+    pub submitter: Option<String>,
+    /// votes on the queued job by voter name
+    #[serde(default)]
+    pub votes: BTreeMap<String, Vote>,
+    /// names of the people who want the job skipped while it is playing
+    #[serde(default)]
+    pub skip_votes: BTreeSet<String>,
     pub metadata: TrackInfo,
     pub job_type: JobType,
+    /// resolved ahead of playback, not journaled since the URLs expire anyway
+    #[serde(skip)]
+    pub streams: Option<Streams>,
+    /// while the file of a `QueueFile` job is being downloaded
+    #[serde(skip)]
+    pub progress: Option<DownloadProgress>,
+    /// only this part of the video is played
+    #[serde(default)]
+    pub clip: Option<Clip>,
+    /// subtitle language instead of the configured ones, empty for no subtitles
+    #[serde(default)]
+    pub subtitles: Option<String>,
+}
+
+/// Everything asked for when a job is queued besides what to play.
+#[derive(Clone, Debug, Default)]
+pub struct JobOptions {
+    /// shown until the job has been resolved
+    pub title: Option<String>,
+    /// also where a resumed video starts
+    pub clip: Option<Clip>,
+    pub subtitles: Option<String>,
+    pub batch_id: Option<usize>,
+    /// name of the user who queued the job, if auth is enabled
+    pub submitter: Option<String>,
+}
+
+/// A part of a video, in seconds from its start.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Clip {
+    pub start: u32,
+    /// `None` plays to the end of the video
+    pub end: Option<u32>,
+}
+
+impl Clip {
+    /// Takes `start` and `end` if given and the timestamps in `url` otherwise, like
+    /// `?t=95`, `&t=1m30s`, `&start=95&end=120` or `#t=1:35`.
+    /// Returns `None` when the whole video is to be played.
+    pub fn new(url: &str, start: Option<u32>, end: Option<u32>) -> anyhow::Result<Option<Self>> {
+        let (url_start, url_end) = url_timestamps(url);
+        let start = start.or(url_start).unwrap_or(0);
+        let end = end.or(url_end);
+        if let Some(end) = end
+            && end <= start
+        {
+            return Err(anyhow::anyhow!(
+                "the clip has to end after it starts, got {start}s to {end}s"
+            ));
+        }
+        Ok((start > 0 || end.is_some()).then_some(Self { start, end }))
+    }
+}
+
+/// Reads the start and end timestamps from the query or the `#t=` fragment of a link.
+fn url_timestamps(url: &str) -> (Option<u32>, Option<u32>) {
+    let Ok(url) = Url::parse(url) else {
+        return (None, None);
+    };
+    let mut start = None;
+    let mut end = None;
+    for (key, value) in url.query_pairs() {
+        match &*key {
+            "t" | "start" | "time_continue" => start = parse_timestamp(&value),
+            "end" => end = parse_timestamp(&value),
+            _ => {}
+        }
+    }
+    // media fragments, `#t=start` or `#t=start,end`
+    if let Some(fragment) = url.fragment().and_then(|f| f.strip_prefix("t=")) {
+        let (from, to) = fragment.split_once(',').unwrap_or((fragment, ""));
+        start = parse_timestamp(from).or(start);
+        end = parse_timestamp(to).or(end);
+    }
+    (start, end)
+}
+
+/// Parses `95`, `95s`, `1m30s`, `1h2m3s`, `1:35` and `1:02:03` into seconds.
+fn parse_timestamp(value: &str) -> Option<u32> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    if value.contains(':') {
+        return value.split(':').try_fold(0u32, |total, part| {
+            total.checked_mul(60)?.checked_add(part.parse().ok()?)
+        });
+    }
+    if let Ok(seconds) = value.parse::<f64>() {
+        return (seconds >= 0.0).then_some(seconds as u32);
+    }
+    let mut total = 0u32;
+    let mut digits = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        let amount: u32 = digits.parse().ok()?;
+        total = total.checked_add(amount.checked_mul(unit)?)?;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        return None;
+    }
+    Some(total)
+}
+
+/// Stream URLs resolved ahead of playback, reused until they expire.
+#[derive(Clone, Debug)]
+pub struct Streams {
+    track: StreamTrack,
+    /// unix timestamp, from the `expire=` parameter of the URLs
+    expires_at: u64,
+}
+
+#[derive(Clone, Debug)]
+enum StreamTrack {
+    Merged(MergedTrack),
+    Split(SplitTrack),
+}
+
+impl Streams {
+    fn new(track: StreamTrack) -> Self {
+        let urls = match &track {
+            StreamTrack::Merged(track) => vec![&track.merged_url],
+            StreamTrack::Split(track) => vec![&track.video_url, &track.audio_url],
+        };
+        let expires_at = urls
+            .into_iter()
+            .map(|url| url_expiry(url))
+            .min()
+            .flatten()
+            .unwrap_or_else(|| unix_timestamp() + DEFAULT_STREAM_LIFETIME_SECS);
+        Self { track, expires_at }
+    }
+}
+
+/// Reads the unix timestamp of the `expire=` query parameter of a stream URL.
+fn url_expiry(url: &str) -> Option<u64> {
+    let url = Url::parse(url).ok()?;
+    url.query_pairs()
+        .find(|(key, _)| key == "expire")
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+/// What the subtitles of a job are named after in the subtitles dir.
+pub fn subtitles_name(job_id: usize) -> String {
+    format!("job{job_id}")
+}
+
+/// Seconds since the unix epoch, used for all job and history timestamps.
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
 impl Job {
-    pub async fn execute(self) -> anyhow::Result<Child> {
+    pub fn start(&mut self) {
+        self.status = JobStatus::Playing;
+        self.started_at = Some(unix_timestamp());
+        self.skip_votes.clear();
+    }
+
+    pub fn count_votes(&self, vote: Vote) -> usize {
+        self.votes.values().filter(|v| **v == vote).count()
+    }
+
+    /// Up votes minus down votes, used to rank the queue in democratic mode.
+    pub fn score(&self) -> i64 {
+        self.count_votes(Vote::Up) as i64 - self.count_votes(Vote::Down) as i64
+    }
+
+    /// Makes the job start at `position`, keeping the end of its clip.
+    pub fn resume_from(&mut self, position: u32) {
+        let end = self.clip.and_then(|clip| clip.end);
+        if end.is_none_or(|end| position < end) {
+            self.clip = Some(Clip {
+                start: position,
+                end,
+            });
+        }
+    }
+
+    /// The subtitles to show, in the job's own language or else the first configured one
+    /// the video has. Written subtitles are preferred over automatic captions.
+    pub fn pick_subtitles(&self, config: &SubtitlesConfig) -> Option<&SubtitleInfo> {
+        let languages = match &self.subtitles {
+            Some(language) if language.is_empty() => return None,
+            Some(language) => std::slice::from_ref(language),
+            None => &config.languages,
+        };
+        languages.iter().find_map(|language| {
+            let mut matching = self
+                .metadata
+                .subtitles
+                .iter()
+                .filter(|subtitles| subtitles.matches(language));
+            matching
+                .clone()
+                .find(|subtitles| !subtitles.auto)
+                .or_else(|| matching.find(|_| config.auto_captions))
+        })
+    }
+
+    pub fn finish(&mut self, status: JobStatus) {
+        self.status = status;
+        self.finished_at = Some(unix_timestamp());
+    }
+
+    /// Whether the streams can still be used if playback starts in `starts_in` seconds.
+    fn streams_valid(&self, starts_in: u64) -> bool {
+        let ends_at =
+            unix_timestamp() + starts_in + self.metadata.duration as u64 + EXPIRY_MARGIN_SECS;
+        self.streams
+            .as_ref()
+            .is_some_and(|streams| streams.expires_at > ends_at)
+    }
+
+    /// Whether the streams have to be resolved again before the job can play in `starts_in`
+    /// seconds without waiting for yt-dlp. Downloaded files never need this.
+    pub fn needs_prefetch(&self, starts_in: u64) -> bool {
+        !matches!(self.job_type, JobType::QueueFile { .. }) && !self.streams_valid(starts_in)
+    }
+
+    /// Looks up the track info, returning the job type with the resolved format (or title) filled
+    /// in and the streams to play.
+    pub async fn resolve(
+        &self,
+        video: &Video,
+        slot: &Slot<'_>,
+    ) -> anyhow::Result<(JobType, TrackInfo, Option<Streams>)> {
+        match &self.job_type {
+            JobType::QueueMerged { url, height, .. } => {
+                let track = video.get_merged_track(url, *height, slot).await?;
+                let job_type = JobType::QueueMerged {
+                    url: url.clone(),
+                    height: *height,
+                    format_id: track.track_info.format_id.clone(),
+                };
+                let track_info = track.track_info.clone();
+                let streams = Streams::new(StreamTrack::Merged(track));
+                Ok((job_type, track_info, Some(streams)))
+            }
+            JobType::QueueSplit { url, height, .. } => {
+                let track = video.get_split_track(url, *height, slot).await?;
+                let job_type = JobType::QueueSplit {
+                    url: url.clone(),
+                    height: *height,
+                    format_id: track.track_info.format_id.clone(),
+                };
+                let track_info = track.track_info.clone();
+                let streams = Streams::new(StreamTrack::Split(track));
+                Ok((job_type, track_info, Some(streams)))
+            }
+            JobType::QueueFile {
+                url, height, file, ..
+            } => {
+                let track = video.get_merged_track(url, *height, slot).await?;
+                let job_type = JobType::QueueFile {
+                    url: url.clone(),
+                    height: *height,
+                    title: track.track_info.title.clone(),
+                    file: file.clone(),
+                };
+                Ok((job_type, track.track_info, None))
+            }
+        }
+    }
+
+    /// Downloads the file of a `QueueFile` job, other job types stream and have nothing to download.
+    pub async fn download(
+        &self,
+        video: &Video,
+        slot: &Slot<'_>,
+        on_progress: impl FnMut(DownloadProgress),
+    ) -> anyhow::Result<()> {
+        match &self.job_type {
+            JobType::QueueFile {
+                url, height, file, ..
+            } => {
+                video
+                    .download_file(file, url, *height, slot, on_progress)
+                    .await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn execute(
+        self,
+        video: &Video,
+        player: &dyn Player,
+        start_at: Option<u32>,
+        stop_at: Option<u32>,
+        subtitles: &SubtitlesConfig,
+    ) -> anyhow::Result<Box<dyn Playback>> {
+        // playback goes on without them if they cannot be downloaded
+        let subtitle_file = match self.pick_subtitles(subtitles) {
+            Some(info) => {
+                info!("job {} shows {} subtitles", self.id, info.lang);
+                video
+                    .download_subtitles(
+                        self.job_type.url(),
+                        info,
+                        &subtitles.dir,
+                        &subtitles_name(self.id),
+                        &video.playback_slot().await,
+                    )
+                    .await
+                    .inspect_err(|e| warn!("failed to download subtitles of job {}: {e}", self.id))
+                    .ok()
+            }
+            None => None,
+        };
+        let subtitle_file = subtitle_file.as_deref();
+        let streams = if self.streams_valid(0) {
+            self.streams.map(|streams| streams.track)
+        } else {
+            None
+        };
         match self.job_type {
+            JobType::QueueMerged { url, height, .. } if player.resolves_urls() => {
+                let title = self.metadata.title;
+                info!("starting {title}");
+                let format = Format::Merged.get_format_string(height);
+                player
+                    .play(
+                        Track::Url { url, format },
+                        &title,
+                        start_at,
+                        stop_at,
+                        subtitle_file,
+                    )
+                    .await
+            }
+            JobType::QueueSplit { url, height, .. } if player.resolves_urls() => {
+                let title = self.metadata.title;
+                info!("starting {title}");
+                let format = Format::Split.get_format_string(height);
+                player
+                    .play(
+                        Track::Url { url, format },
+                        &title,
+                        start_at,
+                        stop_at,
+                        subtitle_file,
+                    )
+                    .await
+            }
+            JobType::QueueMerged { .. } if let Some(StreamTrack::Merged(track)) = streams => {
+                let title = track.track_info.title.clone();
+                info!("starting {title} with prefetched streams");
+                player
+                    .play(
+                        Track::Merged(track),
+                        &title,
+                        start_at,
+                        stop_at,
+                        subtitle_file,
+                    )
+                    .await
+            }
+            JobType::QueueSplit { .. } if let Some(StreamTrack::Split(track)) = streams => {
+                let title = track.track_info.title.clone();
+                info!("starting {title} with prefetched streams");
+                player
+                    .play(
+                        Track::Split(track),
+                        &title,
+                        start_at,
+                        stop_at,
+                        subtitle_file,
+                    )
+                    .await
+            }
             JobType::QueueMerged {
                 url,
                 height,
                 format_id,
             } => {
-                // the first run is just to get the title, we're running it again in case the URLs expire
-                let track = Video::get_merged_track(&url, MinHeight(height.unwrap_or(480))).await?;
+                // the streams expired or were never resolved
+                let track = video
+                    .get_merged_track(&url, height, &video.playback_slot().await)
+                    .await?;
 
                 let curr_format_id = track.track_info.format_id.clone();
                 if curr_format_id != format_id {
@@ -57,8 +524,14 @@ impl Job {
                 let title = track.track_info.title.clone();
                 info!("starting {title}");
 
-                VlcClient::default()
-                    .oneshot(Track::Merged(track), &title)
+                player
+                    .play(
+                        Track::Merged(track),
+                        &title,
+                        start_at,
+                        stop_at,
+                        subtitle_file,
+                    )
                     .await
             }
             JobType::QueueSplit {
@@ -66,8 +539,10 @@ impl Job {
                 height,
                 format_id,
             } => {
-                // the first run is just to get the title, we're running it again in case the URLs expire
-                let track = Video::get_split_track(&url, MinHeight(height.unwrap_or(480))).await?;
+                // the streams expired or were never resolved
+                let track = video
+                    .get_split_track(&url, height, &video.playback_slot().await)
+                    .await?;
 
                 let curr_format_id = track.track_info.format_id.clone();
                 if curr_format_id != format_id {
@@ -80,14 +555,20 @@ impl Job {
                 let title = track.track_info.title.clone();
                 info!("starting {title}");
 
-                VlcClient::default()
-                    .oneshot(Track::Split(track), &title)
+                player
+                    .play(
+                        Track::Split(track),
+                        &title,
+                        start_at,
+                        stop_at,
+                        subtitle_file,
+                    )
                     .await
             }
-            JobType::QueueFile { title, file } => {
+            JobType::QueueFile { title, file, .. } => {
                 info!("starting {title}");
-                VlcClient::default()
-                    .oneshot(Track::File(&file), &title)
+                player
+                    .play(Track::File(&file), &title, start_at, stop_at, subtitle_file)
                     .await
             }
         }
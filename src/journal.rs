@@ -0,0 +1,46 @@
+use std::{collections::VecDeque, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs::{read_to_string, rename, write};
+
+use crate::job::Job;
+
+pub struct Journal {
+    journal_file: PathBuf,
+    contents: JournalContents,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct JournalContents {
+    pub next_job_id: usize,
+    #[serde(default)]
+    pub next_batch_id: usize,
+    pub current: Option<Job>,
+    pub queue: VecDeque<Job>,
+}
+
+impl Journal {
+    pub async fn new(journal_file: PathBuf) -> anyhow::Result<Self> {
+        let contents = match read_to_string(&journal_file).await {
+            Ok(str) => serde_json::from_str::<JournalContents>(&str)?,
+            Err(_) => JournalContents::default(),
+        };
+        Ok(Self {
+            journal_file,
+            contents,
+        })
+    }
+    /// Hands out the contents read on startup, leaving an empty journal behind.
+    pub fn restore(&mut self) -> JournalContents {
+        std::mem::take(&mut self.contents)
+    }
+    pub async fn save(&mut self, contents: JournalContents) -> anyhow::Result<()> {
+        self.contents = contents;
+        // write to a sibling file first so a crash mid-write never leaves a truncated journal
+        let mut temp_file = self.journal_file.clone().into_os_string();
+        temp_file.push(".tmp");
+        write(&temp_file, serde_json::to_string(&self.contents)?).await?;
+        rename(&temp_file, &self.journal_file).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use crate::{
+    auth::Auth,
+    cache::DownloadCache,
+    config::{Config, PlayerBackend, VotesConfig},
+    events::Events,
+    format::MinHeight,
+    history::History,
+    journal::Journal,
+    monitor::PlayerMonitor,
+    mpv::MpvClient,
+    player::Player,
+    queue::QueueManager,
+    rpc::Rpc,
+    search::Search,
+    vlc::VlcClient,
+    yt_dlp::Video,
+};
+
+pub mod api;
+pub mod auth;
+pub mod cache;
+pub mod config;
+pub mod error;
+pub mod events;
+pub mod format;
+pub mod history;
+pub mod job;
+pub mod journal;
+pub mod meta;
+pub mod monitor;
+pub mod mpv;
+pub mod player;
+pub mod queue;
+pub mod rpc;
+pub mod search;
+pub mod vlc;
+pub mod yt_dlp;
+
+pub struct AppState {
+    pub queue: Arc<QueueManager>,
+    pub auth: Auth,
+    pub player: Arc<dyn Player>,
+    pub monitor: Arc<PlayerMonitor>,
+    pub events: Events,
+    pub video: Arc<Video>,
+    pub search: Search,
+    pub default_height: MinHeight,
+    pub votes: VotesConfig,
+    pub cache: Arc<DownloadCache>,
+}
+
+impl AppState {
+    /// Restores history and queue and starts the player and the queue worker.
+    pub async fn new(config: &Config) -> anyhow::Result<Arc<Self>> {
+        let history = History::new(config.history_file.clone()).await?;
+        let journal = Journal::new(config.queue_file.clone()).await?;
+        let video = Arc::new(Video::new(&config.yt_dlp, &config.sponsorblock));
+        let cache = Arc::new(DownloadCache::new(&config.cache).await?);
+        let player: Arc<dyn Player> = match config.player {
+            PlayerBackend::Vlc => {
+                let rpc = Arc::new(Rpc::new(
+                    config.vlc.host.clone(),
+                    config.vlc.port,
+                    config.vlc.password.clone(),
+                ));
+                Arc::new(VlcClient::new(&config.vlc, rpc))
+            }
+            PlayerBackend::Mpv => {
+                Arc::new(MpvClient::new(&config.mpv, config.yt_dlp.binary.clone()))
+            }
+        };
+
+        let events = Events::new();
+        let monitor = Arc::new(PlayerMonitor::new(player.clone(), events.clone()));
+
+        Ok(Arc::new(AppState {
+            queue: Arc::new(QueueManager::new(
+                history,
+                journal,
+                video.clone(),
+                cache.clone(),
+                player.clone(),
+                monitor.clone(),
+                events.clone(),
+                config,
+            )),
+            auth: Auth::new(&config.auth),
+            player,
+            monitor,
+            events,
+            search: Search::new(video.clone(), &config.search),
+            video,
+            default_height: config.default_height(),
+            votes: config.votes.clone(),
+            cache,
+        }))
+    }
+}
@@ -1,10 +1,45 @@
 use serde::Serialize;
 
-use crate::yt_dlp::TrackInfo;
+use crate::{
+    job::{Clip, Job, JobStatus, Vote},
+    yt_dlp::{DownloadProgress, TrackInfo},
+};
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct InspectMetadata {
     pub job_id: usize,
+    pub batch_id: Option<usize>,
+    pub status: JobStatus,
+    pub queued_at: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    pub submitter: Option<String>,
+    pub upvotes: usize,
+    pub downvotes: usize,
+    pub skip_votes: usize,
+    pub progress: Option<DownloadProgress>,
+    pub clip: Option<Clip>,
     pub current: bool,
     pub track_info: TrackInfo,
 }
+
+impl InspectMetadata {
+    pub fn new(job: &Job, current: bool) -> Self {
+        Self {
+            job_id: job.id,
+            batch_id: job.batch_id,
+            status: job.status.clone(),
+            queued_at: job.queued_at,
+            started_at: job.started_at,
+            finished_at: job.finished_at,
+            submitter: job.submitter.clone(),
+            upvotes: job.count_votes(Vote::Up),
+            downvotes: job.count_votes(Vote::Down),
+            skip_votes: job.skip_votes.len(),
+            progress: job.progress.clone(),
+            clip: job.clip,
+            current,
+            track_info: job.metadata.clone(),
+        }
+    }
+}
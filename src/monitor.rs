@@ -0,0 +1,46 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{sync::Mutex, time::interval};
+
+use crate::{
+    events::{Event, Events},
+    player::{Player, PlayerStatus},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Polls the player in the background so that requests and the worker read a cached
+/// status instead of each hitting the player's API.
+pub struct PlayerMonitor {
+    latest: Arc<Mutex<Option<PlayerStatus>>>,
+}
+
+impl PlayerMonitor {
+    pub fn new(player: Arc<dyn Player>, events: Events) -> Self {
+        let latest = Arc::new(Mutex::new(None));
+        let latest_ref = latest.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = interval(POLL_INTERVAL);
+            loop {
+                ticker.tick().await;
+                // the player is not running between jobs, so errors are expected here
+                let status = player.status().await.ok();
+                {
+                    let mut lock = latest_ref.lock().await;
+                    if lock.is_none() && status.is_none() {
+                        continue;
+                    }
+                    *lock = status.clone();
+                }
+                events.send(Event::PlayerState { player: status });
+            }
+        });
+
+        Self { latest }
+    }
+
+    pub async fn latest(&self) -> Option<PlayerStatus> {
+        self.latest.lock().await.clone()
+    }
+}
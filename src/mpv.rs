@@ -0,0 +1,197 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+    process::Command,
+    time::timeout,
+};
+
+use crate::{
+    config::MpvConfig,
+    player::{Playback, Player, PlayerState, PlayerStatus, ProcessPlayback, Seek},
+    yt_dlp::Track,
+};
+
+const IPC_TIMEOUT: Duration = Duration::from_secs(2);
+// mpv answers every request on the connection it was sent on, tagged with this id
+const REQUEST_ID: u64 = 1;
+
+pub struct MpvClient {
+    binary_path: PathBuf,
+    socket_path: PathBuf,
+    hwdec: String,
+    ytdl_hook: bool,
+    yt_dlp_binary: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct IpcReply {
+    request_id: Option<u64>,
+    error: Option<String>,
+    #[serde(default)]
+    data: Value,
+}
+
+impl MpvClient {
+    pub fn new(config: &MpvConfig, yt_dlp_binary: PathBuf) -> Self {
+        Self {
+            binary_path: config.binary.clone(),
+            socket_path: config.socket.clone(),
+            hwdec: config.hwdec.clone(),
+            ytdl_hook: config.ytdl_hook,
+            yt_dlp_binary,
+        }
+    }
+
+    // https://mpv.io/manual/stable/#json-ipc
+    async fn request(&self, command: Value) -> anyhow::Result<Value> {
+        let stream = UnixStream::connect(&self.socket_path).await?;
+        let (read, mut write) = stream.into_split();
+
+        let mut message = serde_json::to_vec(&json!({
+            "command": command,
+            "request_id": REQUEST_ID,
+        }))?;
+        message.push(b'\n');
+        write.write_all(&message).await?;
+
+        let mut lines = BufReader::new(read).lines();
+        let reply = timeout(IPC_TIMEOUT, async {
+            // events are interleaved with the replies, skip them
+            while let Some(line) = lines.next_line().await? {
+                let reply = serde_json::from_str::<IpcReply>(&line)?;
+                if reply.request_id == Some(REQUEST_ID) {
+                    return Ok(reply);
+                }
+            }
+            Err(anyhow::anyhow!("mpv closed the ipc connection"))
+        })
+        .await
+        .map_err(|_| anyhow::anyhow!("mpv did not answer within {IPC_TIMEOUT:?}"))??;
+
+        match reply.error.as_deref() {
+            Some("success") | None => Ok(reply.data),
+            Some(error) => Err(anyhow::anyhow!("mpv command {command} failed: {error}")),
+        }
+    }
+
+    async fn get_property(&self, name: &str) -> anyhow::Result<Value> {
+        self.request(json!(["get_property", name])).await
+    }
+}
+
+#[async_trait]
+impl Player for MpvClient {
+    async fn play(
+        &self,
+        track: Track<'_>,
+        title: &str,
+        start_at: Option<u32>,
+        stop_at: Option<u32>,
+        subtitle_file: Option<&Path>,
+    ) -> anyhow::Result<Box<dyn Playback>> {
+        let mut child = Command::new(&self.binary_path);
+        child
+            .arg(format!("--input-ipc-server={}", self.socket_path.display()))
+            .arg("--fs")
+            .arg(format!("--hwdec={}", self.hwdec))
+            .arg(format!("--force-media-title={title}"));
+
+        if let Some(start_at) = start_at {
+            child.arg(format!("--start={start_at}"));
+        }
+        if let Some(stop_at) = stop_at {
+            child.arg(format!("--end={stop_at}"));
+        }
+        if let Some(subtitle_file) = subtitle_file {
+            child.arg(format!("--sub-file={}", subtitle_file.display()));
+        }
+
+        match track {
+            // stream URLs are already resolved, keep the ytdl hook from looking them up again
+            Track::Merged(merged_track) => child.arg("--ytdl=no").arg(merged_track.merged_url),
+            Track::Split(split_track) => child
+                .arg("--ytdl=no")
+                .arg(format!("--audio-file={}", split_track.audio_url))
+                .arg(split_track.video_url),
+            Track::File(file) => child.arg(file),
+            Track::Url { url, format } => child
+                .arg("--ytdl=yes")
+                .arg(format!("--ytdl-format={format}"))
+                .arg(format!(
+                    "--script-opts=ytdl_hook-ytdl_path={}",
+                    self.yt_dlp_binary.display()
+                ))
+                .arg(url),
+        };
+
+        Ok(Box::new(ProcessPlayback(child.spawn()?)))
+    }
+
+    async fn status(&self) -> anyhow::Result<PlayerStatus> {
+        let paused = self.get_property("pause").await?.as_bool().unwrap_or(false);
+        // position and duration are unavailable while the track is still loading
+        let time = self.get_property("time-pos").await.ok();
+        let length = self.get_property("duration").await.ok();
+        let volume = self.get_property("volume").await?;
+
+        let seconds = |value: Option<Value>| {
+            value
+                .and_then(|value| value.as_f64())
+                .map(|secs| secs.max(0.0) as u32)
+                .unwrap_or(0)
+        };
+
+        Ok(PlayerStatus {
+            state: if paused {
+                PlayerState::Paused
+            } else {
+                PlayerState::Playing
+            },
+            time: seconds(time),
+            length: seconds(length),
+            volume: volume.as_f64().unwrap_or(0.0).round() as u32,
+        })
+    }
+
+    async fn seek(&self, seek: Seek) -> anyhow::Result<()> {
+        let command = match seek {
+            Seek::Relative(secs) => json!(["seek", secs, "relative"]),
+            Seek::Absolute(secs) => json!(["seek", secs, "absolute"]),
+        };
+        self.request(command).await?;
+        Ok(())
+    }
+
+    async fn toggle_pause(&self) -> anyhow::Result<()> {
+        self.request(json!(["cycle", "pause"])).await?;
+        Ok(())
+    }
+
+    async fn set_volume(&self, percent: u32) -> anyhow::Result<()> {
+        self.request(json!(["set_property", "volume", percent]))
+            .await?;
+        Ok(())
+    }
+
+    async fn cycle_subtitles(&self) -> anyhow::Result<()> {
+        self.request(json!(["cycle", "sub"])).await?;
+        Ok(())
+    }
+
+    async fn disable_subtitles(&self) -> anyhow::Result<()> {
+        self.request(json!(["set_property", "sid", "no"])).await?;
+        Ok(())
+    }
+
+    fn resolves_urls(&self) -> bool {
+        self.ytdl_hook
+    }
+}
@@ -0,0 +1,170 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::process::Child;
+
+use crate::{
+    error::RequestError,
+    yt_dlp::{Chapter, Track},
+};
+
+/// A media player the queue can hand tracks to and the API can control.
+#[async_trait]
+pub trait Player: Send + Sync {
+    /// Starts playing the track, either in a new process or in an already running instance.
+    /// Playback starts `start_at` and ends `stop_at` seconds into the track, if given.
+    async fn play(
+        &self,
+        track: Track<'_>,
+        title: &str,
+        start_at: Option<u32>,
+        stop_at: Option<u32>,
+        subtitle_file: Option<&Path>,
+    ) -> anyhow::Result<Box<dyn Playback>>;
+
+    async fn status(&self) -> anyhow::Result<PlayerStatus>;
+
+    async fn seek(&self, seek: Seek) -> anyhow::Result<()>;
+
+    async fn toggle_pause(&self) -> anyhow::Result<()>;
+
+    /// Sets the volume in percent, 100 being the unamplified level.
+    async fn set_volume(&self, percent: u32) -> anyhow::Result<()>;
+
+    /// Switches to the next subtitle track, including none.
+    async fn cycle_subtitles(&self) -> anyhow::Result<()>;
+
+    async fn disable_subtitles(&self) -> anyhow::Result<()>;
+
+    /// Whether the player resolves page URLs itself, in which case it is handed
+    /// `Track::Url` instead of stream URLs looked up by yt-dlp.
+    fn resolves_urls(&self) -> bool {
+        false
+    }
+}
+
+/// A track handed to the player, stopping it is done through here so that a stop
+/// can never hit the track that comes after it.
+#[async_trait]
+pub trait Playback: Send {
+    /// Waits until the track has ended. Not necessarily safe to cancel, keep the same
+    /// future around while doing other things in the meantime.
+    async fn wait(&mut self) -> PlaybackEnd;
+
+    async fn stop(&mut self);
+}
+
+pub enum PlaybackEnd {
+    Finished,
+    Failed(String),
+}
+
+/// A player process started for a single track, which exits once the track has ended.
+pub struct ProcessPlayback(pub Child);
+
+#[async_trait]
+impl Playback for ProcessPlayback {
+    async fn wait(&mut self) -> PlaybackEnd {
+        match self.0.wait().await {
+            Ok(status) if status.success() => PlaybackEnd::Finished,
+            Ok(status) => PlaybackEnd::Failed(format!("player exited with {status}")),
+            Err(e) => PlaybackEnd::Failed(format!("failed to wait for player: {e}")),
+        }
+    }
+
+    async fn stop(&mut self) {
+        let _ = self.0.kill().await;
+    }
+}
+
+pub enum Seek {
+    /// seconds relative to the current position
+    Relative(i32),
+    /// seconds from the start of the track
+    Absolute(u32),
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayerState {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PlayerStatus {
+    pub state: PlayerState,
+    pub time: u32,
+    pub length: u32,
+    /// in percent, see `Player::set_volume`
+    pub volume: u32,
+}
+
+#[derive(Deserialize)]
+pub enum RpcCommand {
+    SeekForward,
+    SeekRewind,
+    SeekTo(u32),
+    TogglePause,
+    Mute,
+    FullVolume,
+    CycleSubtitles,
+    DisableSubtitles,
+    NextChapter,
+    PreviousChapter,
+    /// index into the chapters of the current track
+    SeekToChapter(usize),
+}
+
+/// How far into a chapter `PreviousChapter` goes back to its start instead of the
+/// chapter before it.
+const CHAPTER_RESTART_SECS: u32 = 3;
+
+impl RpcCommand {
+    /// Applies the command to the player, `chapters` are those of the current track.
+    pub async fn apply(self, player: &dyn Player, chapters: &[Chapter]) -> anyhow::Result<()> {
+        match self {
+            RpcCommand::SeekForward => player.seek(Seek::Relative(10)).await,
+            RpcCommand::SeekRewind => player.seek(Seek::Relative(-10)).await,
+            RpcCommand::SeekTo(ts) => player.seek(Seek::Absolute(ts)).await,
+            RpcCommand::TogglePause => player.toggle_pause().await,
+            RpcCommand::Mute => player.set_volume(0).await,
+            RpcCommand::FullVolume => player.set_volume(100).await,
+            RpcCommand::CycleSubtitles => player.cycle_subtitles().await,
+            RpcCommand::DisableSubtitles => player.disable_subtitles().await,
+            RpcCommand::NextChapter => {
+                let time = player.status().await?.time;
+                let next = chapters
+                    .iter()
+                    .find(|chapter| chapter.start > time)
+                    .ok_or_else(|| RequestError::not_found("no chapter after the current one"))?;
+                player.seek(Seek::Absolute(next.start)).await
+            }
+            RpcCommand::PreviousChapter => {
+                let time = player.status().await?.time;
+                if chapters.is_empty() {
+                    return Err(RequestError::not_found("the track has no chapters"));
+                }
+                let current = chapters
+                    .iter()
+                    .rposition(|chapter| chapter.start <= time)
+                    .unwrap_or_default();
+                let target = if time.saturating_sub(chapters[current].start) >= CHAPTER_RESTART_SECS
+                {
+                    current
+                } else {
+                    current.saturating_sub(1)
+                };
+                player.seek(Seek::Absolute(chapters[target].start)).await
+            }
+            RpcCommand::SeekToChapter(index) => {
+                let chapter = chapters
+                    .get(index)
+                    .ok_or_else(|| RequestError::not_found(format!("no chapter {index}")))?;
+                player.seek(Seek::Absolute(chapter.start)).await
+            }
+        }
+    }
+}
@@ -1,156 +1,882 @@
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, HashSet, VecDeque},
+    path::PathBuf,
     sync::{
         Arc,
         atomic::{AtomicBool, AtomicUsize, Ordering},
     },
+    time::Duration,
 };
 
-use tokio::sync::{Mutex, Notify};
+use tokio::{
+    sync::{Mutex, Notify, watch},
+    time::{Instant, interval, sleep},
+};
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::{
+    cache::DownloadCache,
+    config::{Config, ResumeConfig, RetryConfig, Scheduling, SponsorBlockConfig, SubtitlesConfig},
+    error::RequestError,
+    events::{Event, Events},
     history::{History, HistoryEntry},
-    job::{Job, JobType},
+    job::{Job, JobOptions, JobStatus, JobType, Vote, subtitles_name, unix_timestamp},
+    journal::{Journal, JournalContents},
     meta::InspectMetadata,
-    yt_dlp::TrackInfo,
+    monitor::PlayerMonitor,
+    player::{PlaybackEnd, Player, Seek},
+    yt_dlp::{self, Chapter, DownloadProgress, Slot, TrackInfo, TrackType, Video, YtDlpError},
 };
 
+const MAX_FINISHED_LEN: usize = 20;
+// short enough for skip segments to be left soon after they are reached
+const POSITION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
 pub struct QueueManager {
     queue: Arc<Mutex<VecDeque<Job>>>,
     notify: Arc<Notify>,
     running: Arc<Mutex<Option<(Job, CancellationToken)>>>,
+    // job id -> token that stops its download
+    downloads: Arc<Mutex<HashMap<usize, CancellationToken>>>,
     current: Arc<Mutex<Option<(Job, TrackInfo)>>>,
+    // most recently finished first
+    finished: Arc<Mutex<VecDeque<Job>>>,
     clear_requested: Arc<AtomicBool>,
     job_id: Arc<AtomicUsize>,
+    batch_id: Arc<AtomicUsize>,
     history: Arc<Mutex<History>>,
+    journal: Arc<Mutex<Journal>>,
+    video: Arc<Video>,
+    cache: Arc<DownloadCache>,
+    player: Arc<dyn Player>,
+    monitor: Arc<PlayerMonitor>,
+    events: Events,
+    retry: RetryConfig,
+    resume: ResumeConfig,
+    subtitles: SubtitlesConfig,
+    sponsorblock: SponsorBlockConfig,
+    scheduling: Scheduling,
+    prefetch: usize,
 }
 
 impl QueueManager {
-    pub fn new(history: History) -> Self {
-        let notify = Arc::new(Notify::new());
-        let notify_ref = notify.clone();
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        history: History,
+        mut journal: Journal,
+        video: Arc<Video>,
+        cache: Arc<DownloadCache>,
+        player: Arc<dyn Player>,
+        monitor: Arc<PlayerMonitor>,
+        events: Events,
+        config: &Config,
+    ) -> Self {
+        let JournalContents {
+            next_job_id,
+            next_batch_id,
+            current: interrupted,
+            queue: mut restored,
+        } = journal.restore();
+        if let Some(mut job) = interrupted {
+            if config.resume_interrupted {
+                info!("resuming interrupted job {}", job.id);
+                job.status = JobStatus::Ready;
+                job.started_at = None;
+                if let Some(position) = history.position(&job.metadata.webpage_url) {
+                    job.resume_from(position);
+                }
+                restored.push_front(job);
+            } else {
+                info!("dropping interrupted job {}", job.id);
+            }
+        }
+        restored.retain(|job| match &job.job_type {
+            _ if job.status.is_finished() => false,
+            JobType::QueueFile { file, .. } if job.status == JobStatus::Ready && !file.exists() => {
+                warn!(
+                    "dropping job {}: file {} no longer exists",
+                    job.id,
+                    file.display()
+                );
+                false
+            }
+            _ => true,
+        });
+        if !restored.is_empty() {
+            info!("restored {} jobs from journal", restored.len());
+        }
+
+        // resolving and downloading were interrupted by the restart, start them over
+        let mut unresolved = vec![];
+        for job in restored.iter_mut() {
+            if matches!(
+                job.status,
+                JobStatus::Waiting | JobStatus::Resolving | JobStatus::Downloading
+            ) {
+                job.status = JobStatus::Resolving;
+                unresolved.push(job.id);
+            }
+        }
+
+        // never hand out an id that is still in the restored queue
+        let next_job_id = restored
+            .iter()
+            .map(|job| job.id + 1)
+            .fold(next_job_id.max(1), usize::max);
+
+        let manager = QueueManager {
+            queue: Arc::new(Mutex::new(restored)),
+            notify: Arc::new(Notify::new()),
+            running: Arc::new(Mutex::new(None)),
+            downloads: Arc::new(Mutex::new(HashMap::new())),
+            current: Arc::new(Mutex::new(None)),
+            finished: Arc::new(Mutex::new(VecDeque::new())),
+            clear_requested: Arc::new(AtomicBool::new(false)),
+            job_id: Arc::new(AtomicUsize::new(next_job_id)),
+            batch_id: Arc::new(AtomicUsize::new(next_batch_id.max(1))),
+            history: Arc::new(Mutex::new(history)),
+            journal: Arc::new(Mutex::new(journal)),
+            video,
+            cache,
+            player,
+            monitor,
+            events,
+            retry: config.retry.clone(),
+            resume: config.resume.clone(),
+            subtitles: config.subtitles.clone(),
+            sponsorblock: config.sponsorblock.clone(),
+            scheduling: config.scheduling,
+            prefetch: config.prefetch,
+        };
 
-        let queue = Arc::new(Mutex::new(VecDeque::<Job>::new()));
-        let queue_ref = queue.clone();
+        tokio::spawn(manager.clone().run());
 
-        let running = Arc::new(Mutex::new(None));
-        let running_ref = running.clone();
+        if !unresolved.is_empty() {
+            manager.spawn_resolve(unresolved);
+        }
+        manager
+    }
 
-        let clear_requested = Arc::new(AtomicBool::new(false));
-        let clear_ref = clear_requested.clone();
+    async fn run(self) {
+        loop {
+            let mut job = {
+                let mut q = self.queue.lock().await;
+                // jobs that are still resolving stay in place until they are ready
+                let ready = q.iter().position(|job| job.status == JobStatus::Ready);
+                match ready.and_then(|i| q.remove(i)) {
+                    Some(job) => job,
+                    None => {
+                        drop(q);
+                        self.notify.notified().await;
+                        continue;
+                    }
+                }
+            };
 
-        let current = Arc::new(Mutex::new(None));
-        let current_ref = current.clone();
+            info!("starting job {}...", job.id);
+            job.start();
 
-        let history = Arc::new(Mutex::new(history));
-        let history_ref = history.clone();
+            let cancel_token = CancellationToken::new();
+            {
+                let mut lock = self.running.lock().await;
+                *lock = Some((job.clone(), cancel_token.clone()));
+            }
+            {
+                let mut current_lock = self.current.lock().await;
+                *current_lock = Some((job.clone(), job.metadata.clone()));
+            }
+            self.persist().await;
+            self.events.send(Event::JobStarted {
+                job: InspectMetadata::new(&job, true),
+            });
+            self.spawn_prefetch(job.metadata.duration);
 
-        let job_id = Arc::new(AtomicUsize::new(1));
+            let (status, position) = self.play(&job, &cancel_token).await;
+            self.discard_subtitles(job.id).await;
 
-        tokio::spawn(async move {
-            loop {
-                let job = {
-                    let mut q = queue_ref.lock().await;
-                    match q.pop_front() {
-                        Some(job) => job,
-                        None => {
-                            drop(q);
-                            notify_ref.notified().await;
-                            continue;
-                        }
+            if let Some(position) = position {
+                // videos that were played to the end start over next time
+                let resume_at = match status {
+                    JobStatus::Completed => None,
+                    _ => self.resume.resume_at(position, job.metadata.duration),
+                };
+                let mut lock = self.history.lock().await;
+                match lock
+                    .insert(job.metadata.clone(), job.submitter.clone(), resume_at)
+                    .await
+                {
+                    Ok(()) => {
+                        info!("history updated");
+                        self.events.send(Event::HistoryChanged);
                     }
+                    Err(e) => error!("failed to update history: {e}"),
                 };
+            }
 
-                info!("starting job...");
+            {
+                let mut lock = self.running.lock().await;
+                *lock = None;
+            }
+            {
+                let mut current_lock = self.current.lock().await;
+                *current_lock = None;
+            }
 
-                let cancel_token = CancellationToken::new();
-                {
-                    let mut lock = running_ref.lock().await;
-                    *lock = Some((job.clone(), cancel_token.clone()));
+            // a swapped out job was put back into the queue and is not finished yet
+            let requeued = self.find_queued(job.id).await.is_some();
+            if !requeued {
+                job.finish(status);
+                self.record_finished(job).await;
+            }
+
+            if self.clear_requested.load(Ordering::SeqCst) {
+                info!("clearing pending tasks...");
+                let drained: Vec<_> = {
+                    let mut q = self.queue.lock().await;
+                    q.drain(..).collect()
+                };
+                for job in drained {
+                    self.record_cancelled(job).await;
                 }
-                {
-                    let mut current_lock = current_ref.lock().await;
-                    *current_lock = Some((job.clone(), job.metadata.clone()));
+                self.clear_requested.store(false, Ordering::SeqCst);
+            }
+            self.persist().await;
+        }
+    }
+
+    /// Plays the job, restarting the player from the last known position when it exits
+    /// abnormally or stops well before the end of the track.
+    /// Returns the final status and the position playback got to, `None` if the player was
+    /// never started.
+    async fn play(&self, job: &Job, cancel_token: &CancellationToken) -> (JobStatus, Option<u32>) {
+        let duration = job.metadata.duration;
+        let mut reached = None;
+        let mut attempt = 1;
+        let mut start_at = job.clip.map(|clip| clip.start);
+        let stop_at = job.clip.and_then(|clip| clip.end);
+        // where playback is expected to stop, which is the end of the clip if there is one
+        let end_at = stop_at.unwrap_or(duration);
+        // indices of the segments skipped so far, each is only skipped once so that
+        // seeking back into one plays it
+        let mut skipped = HashSet::new();
+
+        loop {
+            let mut last_position = None;
+
+            // the prefetched streams may be what failed, later attempts resolve them again
+            let attempt_job = Job {
+                streams: if attempt == 1 {
+                    job.streams.clone()
+                } else {
+                    None
+                },
+                ..job.clone()
+            };
+            let (reason, code) = match attempt_job
+                .execute(
+                    &self.video,
+                    self.player.as_ref(),
+                    start_at,
+                    stop_at,
+                    &self.subtitles,
+                )
+                .await
+            {
+                Ok(mut playback) => {
+                    let started = Instant::now();
+                    // fall back to the wall clock if the player never answered
+                    let current_position = |last_position: Option<u32>| {
+                        last_position.unwrap_or_else(|| {
+                            start_at.unwrap_or(0) + started.elapsed().as_secs() as u32
+                        })
+                    };
+                    let mut saved_at = Instant::now();
+                    // a single wait for the whole playback, the persistent vlc would lose
+                    // its status polls if it were restarted on every tick
+                    let end = {
+                        let wait = playback.wait();
+                        tokio::pin!(wait);
+                        loop {
+                            tokio::select! {
+                                end = &mut wait => break Some(end),
+                                _ = cancel_token.cancelled() => break None,
+                                _ = sleep(POSITION_POLL_INTERVAL) => {
+                                    if let Some(status) = self.monitor.latest().await
+                                        && status.length > 0
+                                    {
+                                        last_position = Some(status.time);
+                                        if let Some(end) =
+                                            self.skip_segment(job, status.time, &mut skipped).await
+                                        {
+                                            last_position = Some(end);
+                                        }
+                                    }
+                                    if saved_at.elapsed() >= self.resume.save_interval() {
+                                        saved_at = Instant::now();
+                                        self.save_position(job, current_position(last_position))
+                                            .await;
+                                    }
+                                }
+                            }
+                        }
+                    };
+                    let Some(end) = end else {
+                        info!("cancel requested, stopping playback...");
+                        playback.stop().await;
+                        let position = current_position(last_position);
+                        return (JobStatus::Cancelled, Some(position));
+                    };
+
+                    let position = current_position(last_position);
+                    reached = Some(position);
+                    let ended_early =
+                        end_at > 0 && position + self.retry.early_exit_margin_secs < end_at;
+
+                    match end {
+                        PlaybackEnd::Finished if !ended_early => {
+                            info!("task done");
+                            return (JobStatus::Completed, reached);
+                        }
+                        PlaybackEnd::Finished => {
+                            start_at = Some(position);
+                            let reason = format!("playback stopped at {position}s of {end_at}s");
+                            (reason, None)
+                        }
+                        PlaybackEnd::Failed(reason) => {
+                            start_at = last_position.or(start_at);
+                            (reason, None)
+                        }
+                    }
                 }
+                Err(e) => (e.to_string(), YtDlpError::code_of(&e)),
+            };
 
-                let metadata_clone = job.metadata.clone();
+            if attempt >= self.retry.max_attempts {
+                error!("job {} failed after {attempt} attempts: {reason}", job.id);
+                return (JobStatus::Failed { reason, code }, reached);
+            }
 
-                let mut child = match job.execute().await {
-                    Ok(child) => child,
-                    Err(e) => {
-                        error!("failed to start process: {e}");
-                        continue;
+            let backoff = self.retry.backoff(attempt);
+            warn!(
+                "job {} attempt {attempt} failed: {reason}, retrying in {}s",
+                job.id,
+                backoff.as_secs()
+            );
+            tokio::select! {
+                _ = sleep(backoff) => {}
+                _ = cancel_token.cancelled() => return (JobStatus::Cancelled, reached),
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Seeks past the SponsorBlock segment playback is in, if it is one to skip, and
+    /// returns where playback continues.
+    async fn skip_segment(
+        &self,
+        job: &Job,
+        time: u32,
+        skipped: &mut HashSet<usize>,
+    ) -> Option<u32> {
+        let (index, segment) =
+            job.metadata
+                .segments
+                .iter()
+                .enumerate()
+                .find(|(index, segment)| {
+                    segment.start <= time
+                        && time < segment.end
+                        && !skipped.contains(index)
+                        && self.sponsorblock.skips(&segment.category)
+                })?;
+        skipped.insert(index);
+        if let Err(e) = self.player.seek(Seek::Absolute(segment.end)).await {
+            warn!(
+                "failed to skip {} segment of job {}: {e}",
+                segment.category, job.id
+            );
+            return None;
+        }
+        info!(
+            "skipped {} segment of job {} from {}s to {}s",
+            segment.category, job.id, segment.start, segment.end
+        );
+        self.events.send(Event::SegmentSkipped {
+            job_id: job.id,
+            segment: segment.clone(),
+        });
+        Some(segment.end)
+    }
+
+    /// Deletes the subtitles downloaded for a job once it has stopped playing.
+    async fn discard_subtitles(&self, job_id: usize) {
+        let pattern = format!(
+            "{}.*",
+            self.subtitles.dir.join(subtitles_name(job_id)).display()
+        );
+        let Ok(paths) = glob::glob(&pattern) else {
+            return;
+        };
+        for path in paths.flatten() {
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                warn!("failed to remove {}: {e}", path.display());
+            }
+        }
+    }
+
+    /// Saves where the playing job got to in the history, so that it can be resumed after
+    /// a cancel or a restart. Nothing is written while the position stays the same, e.g.
+    /// when playback is paused.
+    async fn save_position(&self, job: &Job, position: u32) {
+        let resume_at = self.resume.resume_at(position, job.metadata.duration);
+        let mut lock = self.history.lock().await;
+        if lock.is_saved(&job.metadata.webpage_url, resume_at) {
+            return;
+        }
+        if let Err(e) = lock
+            .insert(job.metadata.clone(), job.submitter.clone(), resume_at)
+            .await
+        {
+            warn!("failed to save the position of job {}: {e}", job.id);
+        }
+    }
+
+    async fn record_finished(&self, job: Job) {
+        if job.status == JobStatus::Cancelled {
+            self.discard_download(&job).await;
+        }
+        let metadata = InspectMetadata::new(&job, false);
+        {
+            let mut finished = self.finished.lock().await;
+            finished.push_front(job);
+            finished.truncate(MAX_FINISHED_LEN);
+        }
+        self.events.send(match metadata.status {
+            JobStatus::Failed { .. } => Event::JobFailed { job: metadata },
+            _ => Event::JobFinished { job: metadata },
+        });
+    }
+
+    async fn send_queue_order(&self) {
+        let queue = self.queue.lock().await.iter().map(|job| job.id).collect();
+        self.events.send(Event::QueueReordered { queue });
+    }
+
+    async fn record_cancelled(&self, mut job: Job) {
+        info!("cancelled job {}", job.id);
+        if let Some(token) = self.downloads.lock().await.remove(&job.id) {
+            token.cancel();
+        }
+        job.finish(JobStatus::Cancelled);
+        self.record_finished(job).await;
+    }
+
+    async fn persist(&self) {
+        // holding the journal lock keeps snapshots and writes in the same order
+        let mut journal = self.journal.lock().await;
+        let contents = JournalContents {
+            next_job_id: self.job_id.load(Ordering::SeqCst),
+            next_batch_id: self.batch_id.load(Ordering::SeqCst),
+            current: self
+                .current
+                .lock()
+                .await
+                .as_ref()
+                .map(|(job, _)| job.clone()),
+            queue: self.queue.lock().await.clone(),
+        };
+        if let Err(e) = journal.save(contents).await {
+            error!("failed to update journal: {e}");
+        }
+    }
+
+    pub fn scheduling(&self) -> Scheduling {
+        self.scheduling
+    }
+
+    /// Reserves an id that groups several submitted jobs, e.g. the entries of a playlist.
+    pub fn new_batch(&self) -> usize {
+        self.batch_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Queues a job that still has to be resolved and returns its id right away.
+    pub async fn submit(&self, args: JobType, options: JobOptions) -> usize {
+        let ids = self.submit_all(vec![(args, options)]).await;
+        ids[0]
+    }
+
+    /// Queues several jobs at once, they are resolved one after another in the given order.
+    pub async fn submit_all(&self, jobs: Vec<(JobType, JobOptions)>) -> Vec<usize> {
+        let mut ids = vec![];
+        let mut added = vec![];
+        let playing = self
+            .current
+            .lock()
+            .await
+            .as_ref()
+            .map(|(job, _)| job.submitter.clone());
+        {
+            let mut q = self.queue.lock().await;
+            for (args, options) in jobs {
+                let id = self.job_id.fetch_add(1, Ordering::SeqCst);
+                let track_type = match args {
+                    JobType::QueueSplit { .. } => TrackType::Split,
+                    _ => TrackType::Merged,
+                };
+                let index = match self.scheduling {
+                    Scheduling::Fifo => q.len(),
+                    Scheduling::Fair => fair_position(&q, playing.as_ref(), &options.submitter),
+                    // behind everything that was voted up or not voted on yet
+                    Scheduling::Democratic => {
+                        q.iter().position(|job| job.score() < 0).unwrap_or(q.len())
                     }
                 };
+                q.insert(
+                    index,
+                    Job {
+                        id,
+                        batch_id: options.batch_id,
+                        status: JobStatus::Resolving,
+                        queued_at: unix_timestamp(),
+                        started_at: None,
+                        finished_at: None,
+                        submitter: options.submitter,
+                        votes: Default::default(),
+                        skip_votes: Default::default(),
+                        metadata: TrackInfo::pending(args.url(), options.title, track_type),
+                        job_type: args,
+                        streams: None,
+                        progress: None,
+                        clip: options.clip,
+                        subtitles: options.subtitles,
+                    },
+                );
+                ids.push(id);
+                added.push(InspectMetadata::new(&q[index], false));
+            }
+        }
+        self.persist().await;
+        for job in added {
+            self.events.send(Event::JobAdded { job });
+        }
+        if self.scheduling != Scheduling::Fifo {
+            self.send_queue_order().await;
+        }
+        self.spawn_resolve(ids.clone());
+        ids
+    }
 
-                tokio::select! {
-                    result = child.wait() => {
-                        match result {
-                            Ok(status) => info!("task done: {status}"),
-                            Err(e) => error!("wait error: {e}"),
-                        }
+    fn spawn_resolve(&self, job_ids: Vec<usize>) {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            for job_id in job_ids {
+                manager.resolve(job_id).await;
+            }
+        });
+    }
+
+    async fn resolve(&self, job_id: usize) {
+        let Some(job) = self.find_queued(job_id).await else {
+            info!("job {job_id} was removed before it was resolved");
+            return;
+        };
+
+        // held until the download is done as well, so that other jobs cannot take it
+        let slot = self.wait_for_slot(job_id, JobStatus::Resolving).await;
+        let (mut job_type, track_info, streams) = match job.resolve(&self.video, &slot).await {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                error!("failed to resolve job {job_id}: {e}");
+                self.fail_queued(job_id, e).await;
+                return;
+            }
+        };
+
+        let mut slot = Some(slot);
+        // held until the file is downloaded, so that no other job downloads it meanwhile
+        let mut claim = None;
+        let mut needs_download = false;
+        if let JobType::QueueFile { file, height, .. } = &mut job_type {
+            // downloads go to the cache, where the same video may already be waiting
+            let video_id = match track_info.id.as_str() {
+                "" => format!("job{job_id}"),
+                id => id.to_string(),
+            };
+            *file = self.cache.path(&video_id, *height);
+            claim = match self.cache.try_claim_download(file) {
+                Some(claim) => Some(claim),
+                None => {
+                    info!(
+                        "job {job_id} waits for another job downloading {}",
+                        file.display()
+                    );
+                    // the slot is not needed if the other download succeeds
+                    slot = None;
+                    self.set_status(job_id, JobStatus::Downloading).await;
+                    Some(self.cache.claim_download(file).await)
+                }
+            };
+            if self.cache.touch(file).await {
+                info!("job {job_id} reuses {}", file.display());
+                claim = None;
+            } else {
+                needs_download = true;
+            }
+        }
+        let status = if needs_download {
+            JobStatus::Downloading
+        } else {
+            JobStatus::Ready
+        };
+        let resolved = self
+            .update_queued(job_id, |job| {
+                job.job_type = job_type;
+                job.metadata = track_info;
+                job.streams = streams;
+                job.status = status;
+            })
+            .await;
+        if !resolved {
+            info!("job {job_id} was removed while it was resolving");
+            return;
+        }
+
+        if needs_download {
+            let slot = match slot {
+                Some(slot) => slot,
+                None => self.wait_for_slot(job_id, JobStatus::Downloading).await,
+            };
+            let Some(job) = self.find_queued(job_id).await else {
+                return;
+            };
+            match self.download(&job, &slot).await {
+                Some(Ok(())) => {}
+                Some(Err(e)) => {
+                    error!("failed to download job {job_id}: {e}");
+                    if let JobType::QueueFile { file, .. } = &job.job_type {
+                        yt_dlp::remove_partial_download(file).await;
                     }
-                    _ = cancel_token.cancelled() => {
-                        info!("cancel requested, killing child...");
-                        let _ = child.kill().await;
+                    self.fail_queued(job_id, e).await;
+                    return;
+                }
+                None => {
+                    info!("download of job {job_id} was cancelled");
+                    if let JobType::QueueFile { file, .. } = &job.job_type {
+                        yt_dlp::remove_partial_download(file).await;
                     }
+                    return;
                 }
+            }
+            if let JobType::QueueFile { file, .. } = &job.job_type
+                && let Err(e) = self.cache.insert(file).await
+            {
+                warn!("failed to add {} to the cache: {e}", file.display());
+            }
+            if !self.set_status(job_id, JobStatus::Ready).await {
+                info!("job {job_id} was removed while it was downloading");
+                self.discard_download(&job).await;
+                return;
+            }
+            self.cache.evict(&self.files_in_use().await).await;
+            drop(claim);
+        }
 
-                {
-                    let mut lock = history_ref.lock().await;
-                    match lock.insert(metadata_clone).await {
-                        Ok(()) => info!("history updated"),
-                        Err(e) => error!("failed to update history: {e}"),
-                    };
-                }
+        info!("job {job_id} is ready");
+        self.notify.notify_one();
+    }
 
-                {
-                    let mut lock = running_ref.lock().await;
-                    *lock = None;
+    /// Takes a yt-dlp slot for the job, showing it as waiting while every slot is taken
+    /// and as `status` again once it has one.
+    async fn wait_for_slot(&self, job_id: usize, status: JobStatus) -> Slot<'_> {
+        if let Some(slot) = self.video.try_slot() {
+            return slot;
+        }
+        info!("job {job_id} is waiting for a free yt-dlp slot");
+        self.set_status(job_id, JobStatus::Waiting).await;
+        let slot = self.video.slot().await;
+        self.set_status(job_id, status).await;
+        slot
+    }
+
+    /// Downloads the file of a queued job and keeps its progress up to date.
+    /// Returns `None` if the job was cancelled before the download finished.
+    async fn download(&self, job: &Job, slot: &Slot<'_>) -> Option<anyhow::Result<()>> {
+        let token = CancellationToken::new();
+        self.downloads.lock().await.insert(job.id, token.clone());
+
+        let (progress_tx, mut progress_rx) = watch::channel(None);
+        let download = job.download(&self.video, slot, |progress| {
+            progress_tx.send_replace(Some(progress));
+        });
+        tokio::pin!(download);
+
+        let mut ticks = interval(PROGRESS_INTERVAL);
+        let result = loop {
+            tokio::select! {
+                result = &mut download => break Some(result),
+                _ = token.cancelled() => break None,
+                _ = ticks.tick() => {
+                    if progress_rx.has_changed().unwrap_or(false) {
+                        let progress = progress_rx.borrow_and_update().clone();
+                        self.set_progress(job.id, progress).await;
+                    }
                 }
+            }
+        };
+
+        self.downloads.lock().await.remove(&job.id);
+        if result.is_some() {
+            self.set_progress(job.id, None).await;
+        }
+        result
+    }
+
+    /// Like `update_queued`, but without writing the journal, progress is not journaled.
+    async fn set_progress(&self, job_id: usize, progress: Option<DownloadProgress>) {
+        let updated = {
+            let mut q = self.queue.lock().await;
+            let Some(job) = q.iter_mut().find(|job| job.id == job_id) else {
+                return;
+            };
+            job.progress = progress;
+            InspectMetadata::new(job, false)
+        };
+        self.events.send(Event::JobUpdated { job: updated });
+    }
+
+    /// Resolves the streams of the next `prefetch` ready jobs again if they would expire
+    /// before the jobs get to play, so that they start without waiting for yt-dlp.
+    fn spawn_prefetch(&self, playing_duration: u32) {
+        if self.prefetch == 0 || self.player.resolves_urls() {
+            return;
+        }
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let stale: Vec<_> = {
+                let q = manager.queue.lock().await;
+                let mut starts_in = playing_duration as u64;
+                let mut stale = vec![];
+                for job in q
+                    .iter()
+                    .filter(|job| job.status == JobStatus::Ready)
+                    .take(manager.prefetch)
                 {
-                    let mut current_lock = current_ref.lock().await;
-                    *current_lock = None;
+                    if job.needs_prefetch(starts_in) {
+                        stale.push(job.clone());
+                    }
+                    starts_in += job.metadata.duration as u64;
                 }
+                stale
+            };
 
-                if clear_ref.load(Ordering::SeqCst) {
-                    info!("clearing pending tasks...");
-                    {
-                        let mut q = queue_ref.lock().await;
-                        q.clear();
+            for job in stale {
+                let slot = manager.video.slot().await;
+                match job.resolve(&manager.video, &slot).await {
+                    Ok((_, _, streams)) => {
+                        if manager
+                            .update_queued(job.id, |job| job.streams = streams)
+                            .await
+                        {
+                            info!("prefetched streams of job {}", job.id);
+                        }
                     }
-                    clear_ref.store(false, Ordering::SeqCst);
+                    // the job is resolved again when it starts
+                    Err(e) => warn!("failed to prefetch job {}: {e}", job.id),
                 }
             }
         });
+    }
+
+    /// The downloads of the playing and the queued jobs, which must not be evicted.
+    async fn files_in_use(&self) -> HashSet<PathBuf> {
+        let current = self
+            .current
+            .lock()
+            .await
+            .as_ref()
+            .map(|(job, _)| job.clone());
+        let q = self.queue.lock().await;
+        current
+            .iter()
+            .chain(q.iter())
+            .filter_map(|job| match &job.job_type {
+                JobType::QueueFile { file, .. } => Some(file.clone()),
+                _ => None,
+            })
+            .collect()
+    }
 
-        QueueManager {
-            queue,
-            notify,
-            running,
-            current,
-            clear_requested,
-            job_id,
-            history,
+    /// Deletes the download of a cancelled job unless another job still needs it.
+    async fn discard_download(&self, job: &Job) {
+        if let JobType::QueueFile { file, .. } = &job.job_type
+            && !self.files_in_use().await.contains(file)
+        {
+            self.cache.remove(file).await;
         }
     }
 
-    pub async fn submit(&self, args: JobType, metadata: TrackInfo) -> usize {
-        let id = self.job_id.fetch_add(1, Ordering::SeqCst);
+    /// Deletes every cached download that no job needs, returns how many were deleted.
+    pub async fn purge_cache(&self) -> usize {
+        let purged = self.cache.purge(&self.files_in_use().await).await;
+        info!("purged {purged} cached downloads");
+        purged
+    }
 
-        let job = Job {
-            id,
-            metadata,
-            job_type: args,
+    /// Deletes a cached download by file name, returns false if there is none.
+    pub async fn remove_cached(&self, name: &str) -> anyhow::Result<bool> {
+        let Some(file) = self.cache.file(name) else {
+            return Ok(false);
         };
-        {
+        if self.files_in_use().await.contains(&file) {
+            return Err(anyhow::anyhow!("{name} is needed by a queued job"));
+        }
+        Ok(self.cache.remove(&file).await)
+    }
+
+    async fn find_queued(&self, job_id: usize) -> Option<Job> {
+        let q = self.queue.lock().await;
+        q.iter().find(|job| job.id == job_id).cloned()
+    }
+
+    /// Applies `f` to the queued job and persists the queue, returns false if the job is gone.
+    async fn update_queued(&self, job_id: usize, f: impl FnOnce(&mut Job)) -> bool {
+        let updated = {
+            let mut q = self.queue.lock().await;
+            match q.iter_mut().find(|job| job.id == job_id) {
+                Some(job) => {
+                    f(job);
+                    InspectMetadata::new(job, false)
+                }
+                None => return false,
+            }
+        };
+        self.persist().await;
+        self.events.send(Event::JobUpdated { job: updated });
+        true
+    }
+
+    async fn set_status(&self, job_id: usize, status: JobStatus) -> bool {
+        self.update_queued(job_id, |job| job.status = status).await
+    }
+
+    /// Moves a job that could not be made ready from the queue to the finished jobs.
+    async fn fail_queued(&self, job_id: usize, error: anyhow::Error) {
+        let job = {
             let mut q = self.queue.lock().await;
-            q.push_back(job);
+            let index = q.iter().position(|job| job.id == job_id);
+            index.and_then(|i| q.remove(i))
+        };
+        if let Some(mut job) = job {
+            job.finish(JobStatus::Failed {
+                reason: error.to_string(),
+                code: YtDlpError::code_of(&error),
+            });
+            self.record_finished(job).await;
+            self.persist().await;
         }
-        self.notify.notify_one();
-        id
     }
 
     pub async fn cancel_by_id(&self, job_id: usize) -> bool {
@@ -160,8 +886,10 @@ impl QueueManager {
             let index = q.iter().position(|job| job.id == job_id);
 
             if let Some(i) = index {
-                q.remove(i).unwrap();
+                let job = q.remove(i).unwrap();
                 drop(q); // Release the lock early before running async cleanup
+                self.record_cancelled(job).await;
+                self.persist().await;
 
                 info!("cancelled job {job_id} from queue");
                 return true;
@@ -171,12 +899,12 @@ impl QueueManager {
         // Then try to cancel running task
         {
             let lock = self.running.lock().await;
-            if let Some((running_job, token)) = lock.as_ref() {
-                if running_job.id == job_id {
-                    token.cancel();
-                    info!("cancelled currently running job {job_id}");
-                    return true;
-                }
+            if let Some((running_job, token)) = lock.as_ref()
+                && running_job.id == job_id
+            {
+                token.cancel();
+                info!("cancelled currently running job {job_id}");
+                return true;
             }
         }
 
@@ -184,6 +912,35 @@ impl QueueManager {
         false
     }
 
+    /// Removes every queued job of the batch and cancels it if it is currently running.
+    /// Returns the number of cancelled jobs.
+    pub async fn cancel_batch(&self, batch_id: usize) -> usize {
+        let removed: VecDeque<_> = {
+            let mut q = self.queue.lock().await;
+            let (removed, kept) = q.drain(..).partition(|job| job.batch_id == Some(batch_id));
+            *q = kept;
+            removed
+        };
+        let mut cancelled = removed.len();
+        for job in removed {
+            self.record_cancelled(job).await;
+        }
+
+        {
+            let lock = self.running.lock().await;
+            if let Some((running_job, token)) = lock.as_ref()
+                && running_job.batch_id == Some(batch_id)
+            {
+                token.cancel();
+                cancelled += 1;
+            }
+        }
+
+        self.persist().await;
+        info!("cancelled {cancelled} jobs of batch {batch_id}");
+        cancelled
+    }
+
     pub async fn cancel(&self) -> bool {
         let mut lock = self.running.lock().await;
         if let Some((job, token)) = lock.take() {
@@ -213,45 +970,171 @@ impl QueueManager {
         drop(q);
 
         for job in drained_jobs {
-            info!("cancelled job {}", job.id);
+            self.record_cancelled(job).await;
         }
+        self.persist().await;
+        self.events.send(Event::QueueCleared);
 
         self.clear_requested.store(true, Ordering::SeqCst);
     }
 
-    pub async fn inspect(&self) -> (Option<InspectMetadata>, Vec<InspectMetadata>) {
+    pub async fn inspect(
+        &self,
+    ) -> (
+        Option<InspectMetadata>,
+        Vec<InspectMetadata>,
+        Vec<InspectMetadata>,
+    ) {
         let current = self
             .current
             .lock()
             .await
-            .clone()
-            .map(|(job, metadata)| InspectMetadata {
-                job_id: job.id,
-                current: true,
-                track_info: metadata.clone(),
-            });
+            .as_ref()
+            .map(|(job, _)| InspectMetadata::new(job, true));
 
-        let mut curr_queue = vec![];
-        let queue = self.queue.lock().await;
-        for job in queue.iter() {
-            curr_queue.push(InspectMetadata {
-                job_id: job.id,
-                current: false,
-                track_info: job.metadata.clone(),
-            });
+        let queue = {
+            let queue = self.queue.lock().await;
+            queue
+                .iter()
+                .map(|job| InspectMetadata::new(job, false))
+                .collect()
+        };
+
+        let finished = {
+            let finished = self.finished.lock().await;
+            finished
+                .iter()
+                .map(|job| InspectMetadata::new(job, false))
+                .collect()
+        };
+
+        (current, queue, finished)
+    }
+
+    /// Chapters of the track that is playing, empty if nothing is.
+    pub async fn current_chapters(&self) -> Vec<Chapter> {
+        let current = self.current.lock().await;
+        current
+            .as_ref()
+            .map(|(job, _)| job.metadata.chapters.clone())
+            .unwrap_or_default()
+    }
+
+    /// Looks a job up by id, whether it is playing, queued or recently finished.
+    pub async fn get_job(&self, job_id: usize) -> Option<InspectMetadata> {
+        if let Some((job, _)) = self.current.lock().await.as_ref()
+            && job.id == job_id
+        {
+            return Some(InspectMetadata::new(job, true));
         }
+        if let Some(job) = self.find_queued(job_id).await {
+            return Some(InspectMetadata::new(&job, false));
+        }
+        let finished = self.finished.lock().await;
+        finished
+            .iter()
+            .find(|job| job.id == job_id)
+            .map(|job| InspectMetadata::new(job, false))
+    }
 
-        (current, curr_queue)
+    /// Queues a finished job again under a new id, submitted by `submitter`.
+    pub async fn retry(&self, job_id: usize, submitter: Option<String>) -> anyhow::Result<usize> {
+        let job = {
+            let finished = self.finished.lock().await;
+            finished
+                .iter()
+                .find(|job| job.id == job_id)
+                .cloned()
+                .ok_or_else(|| {
+                    RequestError::not_found(format!("job {job_id} is not a finished job"))
+                })?
+        };
+
+        let new_id = self
+            .submit(
+                job.job_type,
+                JobOptions {
+                    title: Some(job.metadata.title),
+                    clip: job.clip,
+                    subtitles: job.subtitles,
+                    batch_id: job.batch_id,
+                    submitter,
+                },
+            )
+            .await;
+        info!("retrying job {job_id} as job {new_id}");
+        Ok(new_id)
+    }
+
+    /// Records `voter`'s vote on a queued job, `None` takes it back. In democratic mode the
+    /// queue is ranked by votes again afterwards.
+    pub async fn vote(
+        &self,
+        job_id: usize,
+        voter: String,
+        vote: Option<Vote>,
+    ) -> anyhow::Result<()> {
+        let found = self
+            .update_queued(job_id, |job| match vote {
+                Some(vote) => {
+                    job.votes.insert(voter, vote);
+                }
+                None => {
+                    job.votes.remove(&voter);
+                }
+            })
+            .await;
+        if !found {
+            return Err(RequestError::not_found(format!(
+                "job {job_id} not found in queue or already running"
+            )));
+        }
+
+        if self.scheduling == Scheduling::Democratic {
+            {
+                let mut q = self.queue.lock().await;
+                // stable, so jobs with the same score keep their order
+                q.make_contiguous()
+                    .sort_by_key(|job| std::cmp::Reverse(job.score()));
+            }
+            self.persist().await;
+            self.send_queue_order().await;
+        }
+        Ok(())
+    }
+
+    /// Adds `voter` to the people who want the current job skipped and cancels it once
+    /// `needed` of them agree. Returns the number of skip votes and whether the job was skipped.
+    pub async fn vote_skip(&self, voter: String, needed: usize) -> anyhow::Result<(usize, bool)> {
+        let (job_id, votes) = {
+            let mut current = self.current.lock().await;
+            let (job, _) = current
+                .as_mut()
+                .ok_or_else(|| RequestError::invalid("nothing is playing"))?;
+            job.skip_votes.insert(voter);
+            self.events.send(Event::JobUpdated {
+                job: InspectMetadata::new(job, true),
+            });
+            (job.id, job.skip_votes.len())
+        };
+
+        if votes < needed {
+            info!("{votes} of {needed} votes to skip job {job_id}");
+            return Ok((votes, false));
+        }
+        info!("skipping job {job_id} after {votes} votes");
+        Ok((votes, self.cancel_by_id(job_id).await))
     }
 
     pub async fn reorder_job(&self, job_id: usize, new_index: usize) -> anyhow::Result<()> {
         let mut q = self.queue.lock().await;
 
         // Find the job in the queue
-        let old_pos = q
-            .iter()
-            .position(|job| job.id == job_id)
-            .ok_or_else(|| anyhow::anyhow!("job {job_id} not found in queue or already running"))?;
+        let old_pos = q.iter().position(|job| job.id == job_id).ok_or_else(|| {
+            RequestError::not_found(format!(
+                "job {job_id} not found in queue or already running"
+            ))
+        })?;
 
         if old_pos == new_index {
             return Ok(());
@@ -269,6 +1152,9 @@ impl QueueManager {
 
         // Convert back to VecDeque
         q.extend(items);
+        drop(q);
+        self.persist().await;
+        self.send_queue_order().await;
 
         info!("reordered job {job_id} from position {old_pos} to position {new_index}");
         Ok(())
@@ -281,17 +1167,23 @@ impl QueueManager {
         let target_index = q
             .iter()
             .position(|job| job.id == job_id)
-            .ok_or_else(|| anyhow::anyhow!("job {job_id} not found in queue"))?;
+            .ok_or_else(|| RequestError::not_found(format!("job {job_id} not found in queue")))?;
+
+        if q[target_index].status != JobStatus::Ready {
+            return Err(RequestError::invalid(format!(
+                "job {job_id} is not ready to play yet"
+            )));
+        }
 
         // Lock currently running job
         let running_lock = self.running.lock().await;
         let (running_job, cancel_token) = running_lock
             .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("no job is currently running"))?
+            .ok_or_else(|| RequestError::invalid("no job is currently running"))?
             .clone();
 
         if running_job.id == job_id {
-            return Err(anyhow::anyhow!("cannot swap a job with itself"));
+            return Err(RequestError::invalid("cannot swap a job with itself"));
         }
 
         // Convert to Vec for manipulation
@@ -304,13 +1196,20 @@ impl QueueManager {
         // Now insert the running job where the target job was
         // Since we removed one item and added it at the front,
         // the original index is now at target_index
-        items.insert(target_index + 1, running_job.clone());
+        let mut requeued_job = running_job.clone();
+        requeued_job.status = JobStatus::Ready;
+        requeued_job.started_at = None;
+        items.insert(target_index + 1, requeued_job);
 
         // Convert back to VecDeque
         q.extend(items);
 
         // Trigger cancellation of the currently running job
         cancel_token.cancel();
+        drop(running_lock);
+        drop(q);
+        self.persist().await;
+        self.send_queue_order().await;
 
         info!(
             "swapped running job {} with queued job {}",
@@ -324,9 +1223,46 @@ impl QueueManager {
         let lock = self.history.lock().await;
         lock.get_history()
     }
+    /// Where playback of the video behind `link` stopped the last time, if it is worth
+    /// resuming from.
+    pub async fn resume_position(&self, link: &str) -> Option<u32> {
+        self.history.lock().await.position(link)
+    }
+
     pub async fn remove_history_entry(&self, webpage_url: &str) -> anyhow::Result<()> {
         let mut lock = self.history.lock().await;
         lock.remove(webpage_url).await?;
+        self.events.send(Event::HistoryChanged);
         Ok(())
     }
 }
+
+/// Where a job of `submitter` goes when people take turns: the n-th job someone has
+/// queued (counting the one that is playing) is played in round n, after every job of
+/// the earlier rounds and the jobs already queued for the same round. Jobs that were
+/// moved by hand keep their place, new jobs are only ever inserted.
+fn fair_position(
+    queue: &VecDeque<Job>,
+    playing: Option<&Option<String>>,
+    submitter: &Option<String>,
+) -> usize {
+    let mut rounds: HashMap<&Option<String>, usize> = HashMap::new();
+    if let Some(playing) = playing {
+        rounds.insert(playing, 1);
+    }
+    let round = rounds.get(submitter).copied().unwrap_or(0)
+        + queue
+            .iter()
+            .filter(|job| &job.submitter == submitter)
+            .count();
+
+    let mut position = 0;
+    for (i, job) in queue.iter().enumerate() {
+        let job_round = rounds.entry(&job.submitter).or_default();
+        if *job_round <= round {
+            position = i + 1;
+        }
+        *job_round += 1;
+    }
+    position
+}
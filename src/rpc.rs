@@ -1,7 +1,7 @@
-use std::collections::HashMap;
-
 use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
+
+use crate::player::{PlayerState, PlayerStatus};
 
 pub struct Rpc {
     url: String,
@@ -9,62 +9,25 @@ pub struct Rpc {
     client: Client,
 }
 
-#[derive(Deserialize, Serialize)]
-pub enum State {
-    #[serde(rename = "playing")]
-    Playing,
-    #[serde(rename = "paused")]
-    Paused,
-}
-
-#[derive(Deserialize, Serialize)]
-pub struct RpcResponse {
-    state: State,
-    time: u32,
-    length: u32,
-    volume: u16,
-}
+// vlc's volume for 100%
+pub const VOLUME_SCALE: u32 = 256;
 
 #[derive(Deserialize)]
-pub enum RpcCommand {
-    SeekForward,
-    SeekRewind,
-    SeekTo(u32),
-    TogglePause,
-    Mute,
-    FullVolume,
+pub struct VlcStatus {
+    pub state: PlayerState,
+    pub time: u32,
+    pub length: u32,
+    pub volume: u32,
 }
 
-impl RpcCommand {
-    fn to_query_string(&self) -> String {
-        let mut map: HashMap<&'static str, String> = HashMap::new();
-        match self {
-            RpcCommand::SeekForward => {
-                map.insert("command", "seek".into());
-                map.insert("val", "+10".into());
-            }
-            RpcCommand::SeekRewind => {
-                map.insert("command", "seek".into());
-                map.insert("val", "-10".into());
-            }
-            RpcCommand::SeekTo(ts) => {
-                map.insert("command", "seek".into());
-                map.insert("val", ts.to_string());
-            }
-            RpcCommand::TogglePause => {
-                map.insert("command", "pl_pause".into());
-            }
-            RpcCommand::Mute => {
-                map.insert("command", "volume".into());
-                map.insert("val", "0".to_string());
-            }
-            RpcCommand::FullVolume => {
-                map.insert("command", "volume".into());
-                map.insert("val", "255".to_string());
-            }
-        };
-
-        serde_urlencoded::to_string(map).unwrap()
+impl From<VlcStatus> for PlayerStatus {
+    fn from(status: VlcStatus) -> Self {
+        Self {
+            state: status.state,
+            time: status.time,
+            length: status.length,
+            volume: (status.volume * 100 + VOLUME_SCALE / 2) / VOLUME_SCALE,
+        }
     }
 }
 
@@ -79,25 +42,54 @@ impl Rpc {
         }
     }
 
-    pub async fn get_status(&self) -> anyhow::Result<RpcResponse> {
+    pub async fn get_status(&self) -> anyhow::Result<VlcStatus> {
         let response = self
             .client
             .get(&self.url)
             .basic_auth("", Some(&self.password))
             .send()
             .await?;
-        let json = response.json::<RpcResponse>().await?;
+        let json = response.json::<VlcStatus>().await?;
         Ok(json)
     }
 
-    pub async fn execute_command(&self, command: RpcCommand) -> anyhow::Result<RpcResponse> {
+    /// Sends a single command such as `seek` or `pl_pause`, with an optional `val` argument.
+    pub async fn command(&self, command: &str, val: Option<&str>) -> anyhow::Result<VlcStatus> {
+        let mut query = vec![("command", command)];
+        if let Some(val) = val {
+            query.push(("val", val));
+        }
+        self.send(serde_urlencoded::to_string(query)?).await
+    }
+
+    /// Replaces the playlist with `input` and starts playing it.
+    /// `options` are input options such as `:start-time=30`.
+    pub async fn play_input(&self, input: &str, options: &[String]) -> anyhow::Result<()> {
+        self.send(serde_urlencoded::to_string([("command", "pl_empty")])?)
+            .await?;
+
+        let mut query = vec![("command", "in_play"), ("input", input)];
+        query.extend(options.iter().map(|option| ("option", option.as_str())));
+        self.send(serde_urlencoded::to_string(query)?).await?;
+        Ok(())
+    }
+
+    pub async fn stop(&self) -> anyhow::Result<()> {
+        self.send(serde_urlencoded::to_string([("command", "pl_stop")])?)
+            .await?;
+        self.send(serde_urlencoded::to_string([("command", "pl_empty")])?)
+            .await?;
+        Ok(())
+    }
+
+    async fn send(&self, query: String) -> anyhow::Result<VlcStatus> {
         let response = self
             .client
-            .get(format!("{}?{}", self.url, command.to_query_string()))
+            .get(format!("{}?{}", self.url, query))
             .basic_auth("", Some(&self.password))
             .send()
             .await?;
-        let json = response.json::<RpcResponse>().await?;
+        let json = response.json::<VlcStatus>().await?;
         Ok(json)
     }
 }
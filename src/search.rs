@@ -0,0 +1,77 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tracing::info;
+
+use crate::{
+    config::SearchConfig,
+    error::RequestError,
+    yt_dlp::{SearchResult, Video},
+};
+
+const CACHE_CAPACITY: usize = 100;
+
+// normalized query and limit
+type CacheKey = (String, usize);
+
+/// Searches through yt-dlp and remembers the results of recent queries.
+pub struct Search {
+    video: Arc<Video>,
+    max_results: usize,
+    ttl: Duration,
+    // when the query was searched and what was found
+    cache: Mutex<HashMap<CacheKey, (Instant, Vec<SearchResult>)>>,
+}
+
+impl Search {
+    pub fn new(video: Arc<Video>, config: &SearchConfig) -> Self {
+        Self {
+            video,
+            max_results: config.max_results,
+            ttl: Duration::from_secs(config.cache_secs),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn max_results(&self) -> usize {
+        self.max_results
+    }
+
+    /// Returns up to `limit` results (capped at `max_results`), from the cache if the same
+    /// query was searched recently.
+    pub async fn search(&self, query: &str, limit: usize) -> anyhow::Result<Vec<SearchResult>> {
+        let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+        if query.is_empty() {
+            return Err(RequestError::invalid("the search query is empty"));
+        }
+        let key = (query.to_lowercase(), limit.clamp(1, self.max_results));
+
+        if let Some((searched_at, results)) = self.cache.lock().unwrap().get(&key)
+            && searched_at.elapsed() < self.ttl
+        {
+            return Ok(results.clone());
+        }
+
+        info!("searching for {query}...");
+        let results = {
+            let slot = self.video.lookup_slot().await?;
+            self.video.search(&query, key.1, &slot).await?
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (searched_at, _)| searched_at.elapsed() < self.ttl);
+        if cache.len() >= CACHE_CAPACITY
+            && let Some(oldest) = cache
+                .iter()
+                .min_by_key(|(_, (searched_at, _))| *searched_at)
+                .map(|(key, _)| key.clone())
+        {
+            cache.remove(&oldest);
+        }
+        cache.insert(key, (Instant::now(), results.clone()));
+        Ok(results)
+    }
+}
@@ -1,40 +1,93 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
 
-use tokio::process::{Child, Command};
+use async_trait::async_trait;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::{Child, Command},
+    time::{Instant, sleep},
+};
+use tracing::{error, info, warn};
 
-use crate::yt_dlp::Track;
+use crate::{
+    config::{VlcConfig, VlcMode},
+    player::{Playback, PlaybackEnd, Player, PlayerState, PlayerStatus, ProcessPlayback, Seek},
+    rpc::{Rpc, VOLUME_SCALE},
+    yt_dlp::Track,
+};
+
+const RESTART_DELAY: Duration = Duration::from_secs(2);
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+// how long a loaded track may take to start playing before it counts as failed
+const START_TIMEOUT: Duration = Duration::from_secs(20);
+const MAX_STATUS_ERRORS: u32 = 5;
 
 pub struct VlcClient {
     binary_path: PathBuf,
+    http_host: String,
+    http_port: u16,
+    http_password: String,
+    rpc: Arc<Rpc>,
+    // only set in persistent mode, bumped every time the supervisor (re)starts vlc
+    generation: Option<Arc<AtomicUsize>>,
 }
 
-impl Default for VlcClient {
-    fn default() -> Self {
-        let binary_path = if cfg!(target_os = "macos") {
-            "/Applications/VLC.app/Contents/MacOS/VLC".into()
-        } else if cfg!(target_os = "linux") {
-            "vlc".into()
-        } else {
-            unimplemented!()
+impl VlcClient {
+    pub fn new(config: &VlcConfig, rpc: Arc<Rpc>) -> Self {
+        let mut client = Self {
+            binary_path: config.binary.clone(),
+            http_host: config.host.clone(),
+            http_port: config.port,
+            http_password: config.password.clone(),
+            rpc,
+            generation: None,
         };
-        Self { binary_path }
+        if config.mode == VlcMode::Persistent {
+            client.generation = Some(client.launch_persistent());
+        }
+        client
     }
-}
 
-impl VlcClient {
-    // pub fn with_binary_path(binary_path: PathBuf) -> Self {
-    //     Self { binary_path }
-    // }
-    pub async fn oneshot<'a>(&self, track: Track<'a>, title: &str) -> anyhow::Result<Child> {
+    fn http_args(&self) -> [String; 4] {
+        [
+            "--extraintf=http".into(),
+            format!("--http-password={}", self.http_password),
+            format!("--http-host={}", self.http_host),
+            format!("--http-port={}", self.http_port),
+        ]
+    }
+
+    async fn oneshot(
+        &self,
+        track: Track<'_>,
+        title: &str,
+        start_at: Option<u32>,
+        stop_at: Option<u32>,
+        subtitle_file: Option<&Path>,
+    ) -> anyhow::Result<Child> {
         let binary_path = self.binary_path.clone();
         let mut child = Command::new(binary_path);
         child
             .arg("--play-and-exit")
             .arg("--fullscreen")
-            .arg("--extraintf=http")
-            .arg("--http-password=abc")
-            .arg("--http-host=0.0.0.0")
-            .arg("--http-port=8081");
+            .args(self.http_args());
+
+        if let Some(start_at) = start_at {
+            child.arg(format!("--start-time={start_at}"));
+        }
+        if let Some(stop_at) = stop_at {
+            child.arg(format!("--stop-time={stop_at}"));
+        }
+        if let Some(subtitle_file) = subtitle_file {
+            child.arg(format!("--sub-file={}", subtitle_file.display()));
+        }
 
         match track {
             Track::Merged(merged_track) => child
@@ -48,47 +101,225 @@ impl VlcClient {
                 .arg(split_track.audio_url)
                 .arg(split_track.video_url),
             Track::File(file) => child.arg("--meta-title").arg(title).arg(file),
+            Track::Url { url, .. } => child.arg("--meta-title").arg(title).arg(url),
         };
 
         Ok(child.spawn()?)
     }
-    // pub async fn launch_persistent_with_http_api(&self) -> anyhow::Result<()> {
-    //     let binary_path = self.binary_path.clone();
-    //     tokio::spawn(async move {
-    //         let mut cmd = Command::new(binary_path);
-    //         let cmd = cmd
-    //             .arg("--extraintf=http")
-    //             .arg("--http-password=abc")
-    //             .arg("--http-host=0.0.0.0")
-    //             .arg("--http-port=8081");
-
-    //         cmd.stdout(Stdio::piped());
-    //         cmd.stderr(Stdio::piped());
-
-    //         let mut child = cmd.spawn().expect("failed to spawn vlc");
-
-    //         let stdout = child
-    //             .stdout
-    //             .take()
-    //             .expect("child did not have a handle to stdout");
-    //         let stderr = child
-    //             .stderr
-    //             .take()
-    //             .expect("child did not have a handle to stderr");
-
-    //         tokio::spawn(async move {
-    //             let mut lines = BufReader::new(stdout).lines();
-    //             while let Ok(Some(line)) = lines.next_line().await {
-    //                 info!("[vlc::stdout] {line}")
-    //             }
-    //         });
-    //         tokio::spawn(async move {
-    //             let mut lines = BufReader::new(stderr).lines();
-    //             while let Ok(Some(line)) = lines.next_line().await {
-    //                 info!("[vlc::stderr] {line}")
-    //             }
-    //         });
-    //     });
-    //     Ok(())
-    // }
+
+    async fn load(
+        &self,
+        track: Track<'_>,
+        title: &str,
+        start_at: Option<u32>,
+        stop_at: Option<u32>,
+        subtitle_file: Option<&Path>,
+        generation: &Arc<AtomicUsize>,
+    ) -> anyhow::Result<PlaylistItem> {
+        let mut options = vec![format!(":meta-title={title}")];
+        if let Some(start_at) = start_at {
+            options.push(format!(":start-time={start_at}"));
+        }
+        if let Some(stop_at) = stop_at {
+            options.push(format!(":stop-time={stop_at}"));
+        }
+        if let Some(subtitle_file) = subtitle_file {
+            options.push(format!(":sub-file={}", subtitle_file.display()));
+        }
+        let input = match track {
+            Track::Merged(merged_track) => merged_track.merged_url,
+            Track::Split(split_track) => {
+                options.push(format!(":input-slave={}", split_track.audio_url));
+                split_track.video_url
+            }
+            Track::File(file) => file.display().to_string(),
+            Track::Url { url, .. } => url,
+        };
+
+        // vlc might still be starting up after a crash
+        let deadline = Instant::now() + START_TIMEOUT;
+        loop {
+            match self.rpc.play_input(&input, &options).await {
+                Ok(()) => break,
+                Err(e) if Instant::now() < deadline => {
+                    warn!("vlc is not ready yet: {e}");
+                    sleep(STATUS_POLL_INTERVAL).await;
+                }
+                Err(e) => return Err(anyhow::anyhow!("failed to load track into vlc: {e}")),
+            }
+        }
+
+        Ok(PlaylistItem {
+            rpc: self.rpc.clone(),
+            generation: generation.clone(),
+            loaded_generation: generation.load(Ordering::SeqCst),
+            loaded_at: Instant::now(),
+            started: false,
+            errors: 0,
+        })
+    }
+
+    /// Starts a long-lived vlc and restarts it whenever it exits.
+    fn launch_persistent(&self) -> Arc<AtomicUsize> {
+        let generation = Arc::new(AtomicUsize::new(0));
+        let generation_ref = generation.clone();
+
+        let binary_path = self.binary_path.clone();
+        let http_args = self.http_args();
+
+        tokio::spawn(async move {
+            loop {
+                generation_ref.fetch_add(1, Ordering::SeqCst);
+
+                let mut cmd = Command::new(&binary_path);
+                cmd.arg("--fullscreen")
+                    .arg("--no-video-title-show")
+                    .args(&http_args)
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .kill_on_drop(true);
+
+                let mut child = match cmd.spawn() {
+                    Ok(child) => child,
+                    Err(e) => {
+                        error!("failed to start persistent vlc: {e}");
+                        sleep(RESTART_DELAY).await;
+                        continue;
+                    }
+                };
+                info!("started persistent vlc");
+
+                if let Some(stdout) = child.stdout.take() {
+                    tokio::spawn(async move {
+                        let mut lines = BufReader::new(stdout).lines();
+                        while let Ok(Some(line)) = lines.next_line().await {
+                            info!("[vlc::stdout] {line}")
+                        }
+                    });
+                }
+                if let Some(stderr) = child.stderr.take() {
+                    tokio::spawn(async move {
+                        let mut lines = BufReader::new(stderr).lines();
+                        while let Ok(Some(line)) = lines.next_line().await {
+                            info!("[vlc::stderr] {line}")
+                        }
+                    });
+                }
+
+                match child.wait().await {
+                    Ok(status) => error!("persistent vlc exited with {status}, restarting..."),
+                    Err(e) => error!("failed to wait for persistent vlc: {e}, restarting..."),
+                }
+                sleep(RESTART_DELAY).await;
+            }
+        });
+
+        generation
+    }
+}
+
+#[async_trait]
+impl Player for VlcClient {
+    async fn play(
+        &self,
+        track: Track<'_>,
+        title: &str,
+        start_at: Option<u32>,
+        stop_at: Option<u32>,
+        subtitle_file: Option<&Path>,
+    ) -> anyhow::Result<Box<dyn Playback>> {
+        match &self.generation {
+            None => Ok(Box::new(ProcessPlayback(
+                self.oneshot(track, title, start_at, stop_at, subtitle_file)
+                    .await?,
+            ))),
+            Some(generation) => Ok(Box::new(
+                self.load(track, title, start_at, stop_at, subtitle_file, generation)
+                    .await?,
+            )),
+        }
+    }
+
+    async fn status(&self) -> anyhow::Result<PlayerStatus> {
+        Ok(self.rpc.get_status().await?.into())
+    }
+
+    async fn seek(&self, seek: Seek) -> anyhow::Result<()> {
+        let val = match seek {
+            Seek::Relative(secs) => format!("{secs:+}"),
+            Seek::Absolute(secs) => secs.to_string(),
+        };
+        self.rpc.command("seek", Some(&val)).await?;
+        Ok(())
+    }
+
+    async fn toggle_pause(&self) -> anyhow::Result<()> {
+        self.rpc.command("pl_pause", None).await?;
+        Ok(())
+    }
+
+    async fn set_volume(&self, percent: u32) -> anyhow::Result<()> {
+        let val = (percent * VOLUME_SCALE / 100).to_string();
+        self.rpc.command("volume", Some(&val)).await?;
+        Ok(())
+    }
+
+    async fn cycle_subtitles(&self) -> anyhow::Result<()> {
+        self.rpc.command("key", Some("subtitle-track")).await?;
+        Ok(())
+    }
+
+    async fn disable_subtitles(&self) -> anyhow::Result<()> {
+        self.rpc.command("subtitle_track", Some("-1")).await?;
+        Ok(())
+    }
+}
+
+/// A track loaded into the persistent vlc instance.
+pub struct PlaylistItem {
+    rpc: Arc<Rpc>,
+    generation: Arc<AtomicUsize>,
+    loaded_generation: usize,
+    loaded_at: Instant,
+    started: bool,
+    errors: u32,
+}
+
+#[async_trait]
+impl Playback for PlaylistItem {
+    async fn wait(&mut self) -> PlaybackEnd {
+        loop {
+            sleep(STATUS_POLL_INTERVAL).await;
+
+            if self.generation.load(Ordering::SeqCst) != self.loaded_generation {
+                return PlaybackEnd::Failed("vlc crashed during playback".into());
+            }
+
+            match self.rpc.get_status().await {
+                Ok(status) => {
+                    self.errors = 0;
+                    match status.state {
+                        PlayerState::Playing | PlayerState::Paused => self.started = true,
+                        PlayerState::Stopped if self.started => return PlaybackEnd::Finished,
+                        PlayerState::Stopped if self.loaded_at.elapsed() > START_TIMEOUT => {
+                            return PlaybackEnd::Failed("vlc did not start playing".into());
+                        }
+                        PlayerState::Stopped => {}
+                    }
+                }
+                Err(e) => {
+                    self.errors += 1;
+                    if self.errors >= MAX_STATUS_ERRORS {
+                        return PlaybackEnd::Failed(format!("lost connection to vlc: {e}"));
+                    }
+                }
+            }
+        }
+    }
+
+    async fn stop(&mut self) {
+        if let Err(e) = self.rpc.stop().await {
+            error!("failed to stop vlc: {e}");
+        }
+    }
 }
@@ -1,55 +1,328 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Output, Stdio},
+    time::Duration,
+};
 
 use glob::glob;
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
-use tempfile::NamedTempFile;
-use tokio::process::Command;
-use tracing::{error, info};
-
-use crate::format::{Format, MinHeight};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+    process::Command,
+    sync::{Semaphore, SemaphorePermit},
+    time::timeout,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{SponsorBlockConfig, YtDlpConfig},
+    format::{Format, MinHeight},
+};
+
+/// Permission to run one yt-dlp process, every call that runs yt-dlp asks for one so
+/// that the number of processes stays bounded.
+pub struct Slot<'a> {
+    _permit: SemaphorePermit<'a>,
+}
 
-pub struct Video;
+pub struct Video {
+    binary_path: PathBuf,
+    timeout: Duration,
+    download_timeout: Duration,
+    // one permit per yt-dlp process that may run in the background
+    slots: Semaphore,
+    // kept apart so that the track about to play never waits behind long downloads
+    playback_slot: Semaphore,
+    // passed along when resolving videos, empty if SponsorBlock is disabled
+    sponsorblock_args: Vec<String>,
+    max_playlist_entries: usize,
+}
 
 impl Video {
+    pub fn new(config: &YtDlpConfig, sponsorblock: &SponsorBlockConfig) -> Self {
+        let mut sponsorblock_args = vec![];
+        if sponsorblock.enabled && !sponsorblock.skip.is_empty() {
+            // only looks the segments up, the video is never downloaded with them cut
+            sponsorblock_args.push("--sponsorblock-mark".into());
+            sponsorblock_args.push(sponsorblock.skip.join(","));
+            if let Some(api_url) = &sponsorblock.api_url {
+                sponsorblock_args.push("--sponsorblock-api".into());
+                sponsorblock_args.push(api_url.clone());
+            }
+        }
+        Self {
+            binary_path: config.binary.clone(),
+            timeout: config.timeout(),
+            download_timeout: config.download_timeout(),
+            slots: Semaphore::new(config.max_processes),
+            playback_slot: Semaphore::new(1),
+            sponsorblock_args,
+            max_playlist_entries: config.max_playlist_entries,
+        }
+    }
+
+    /// Takes a slot for running yt-dlp in the background if one is free.
+    pub fn try_slot(&self) -> Option<Slot<'_>> {
+        self.slots
+            .try_acquire()
+            .ok()
+            .map(|permit| Slot { _permit: permit })
+    }
+
+    /// Waits for a slot for running yt-dlp in the background, slots are handed out in
+    /// the order they were asked for.
+    pub async fn slot(&self) -> Slot<'_> {
+        if let Some(slot) = self.try_slot() {
+            return slot;
+        }
+        info!("waiting for a free yt-dlp slot");
+        let permit = self
+            .slots
+            .acquire()
+            .await
+            .expect("the yt-dlp semaphore is never closed");
+        Slot { _permit: permit }
+    }
+
+    /// Waits for a slot like [`Video::slot`], but gives up after the lookup timeout so that
+    /// requests do not hang while every slot is busy with a long download.
+    pub async fn lookup_slot(&self) -> anyhow::Result<Slot<'_>> {
+        timeout(self.timeout, self.slot()).await.map_err(|_| {
+            YtDlpError {
+                code: ErrorCode::Timeout,
+                message: format!(
+                    "no yt-dlp slot became free within {}s",
+                    self.timeout.as_secs()
+                ),
+            }
+            .into()
+        })
+    }
+
+    /// Waits for the slot kept for looking up the track that is about to play, which is
+    /// not taken by background resolves and downloads.
+    pub async fn playback_slot(&self) -> Slot<'_> {
+        let permit = self
+            .playback_slot
+            .acquire()
+            .await
+            .expect("the yt-dlp semaphore is never closed");
+        Slot { _permit: permit }
+    }
+
+    /// Runs yt-dlp to completion, killing it after `timeout`.
+    async fn output(&self, command: &mut Command) -> anyhow::Result<Output> {
+        let output = timeout(self.timeout, command.kill_on_drop(true).output())
+            .await
+            .map_err(|_| YtDlpError::timed_out(self.timeout))??;
+        YtDlpError::check(&output)?;
+        Ok(output)
+    }
+
     async fn get_json(
+        &self,
         link: &str,
         format: Format,
         min_height: MinHeight,
+        _slot: &Slot<'_>,
     ) -> anyhow::Result<JsonDump> {
-        let output = Command::new("yt-dlp")
-            .arg("-f")
-            .arg(format.get_format_string(min_height))
-            .arg("--skip-download")
-            .arg("--dump-json")
-            .arg(link)
-            .output()
-            .await?
-            .stdout;
-        let json = String::from_utf8(output)?.trim().to_string();
+        let output = self
+            .output(
+                Command::new(&self.binary_path)
+                    .arg("-f")
+                    .arg(format.get_format_string(min_height))
+                    .arg("--skip-download")
+                    .arg("--dump-json")
+                    .args(&self.sponsorblock_args)
+                    .arg(link),
+            )
+            .await?;
+        let json = String::from_utf8(output.stdout)?.trim().to_string();
         let dump = serde_json::from_str::<JsonDump>(&json)?;
         Ok(dump)
     }
 
     pub async fn get_merged_track(
+        &self,
         link: &str,
         min_height: MinHeight,
+        slot: &Slot<'_>,
     ) -> anyhow::Result<MergedTrack> {
-        let json = Self::get_json(link, Format::Merged, min_height).await?;
+        let json = self
+            .get_json(link, Format::Merged, min_height, slot)
+            .await?;
         json.try_into()
     }
 
-    pub async fn get_split_track(link: &str, min_height: MinHeight) -> anyhow::Result<SplitTrack> {
-        let json = Self::get_json(link, Format::Split, min_height).await?;
+    pub async fn get_split_track(
+        &self,
+        link: &str,
+        min_height: MinHeight,
+        slot: &Slot<'_>,
+    ) -> anyhow::Result<SplitTrack> {
+        let json = self.get_json(link, Format::Split, min_height, slot).await?;
         json.try_into()
     }
 
+    /// Lists the entries of a playlist or channel without resolving each video.
+    pub async fn get_playlist(
+        &self,
+        link: &str,
+        range: PlaylistRange,
+        _slot: &Slot<'_>,
+    ) -> anyhow::Result<Vec<PlaylistEntry>> {
+        let output = self
+            .output(
+                Command::new(&self.binary_path)
+                    .arg("--flat-playlist")
+                    .arg("--dump-single-json")
+                    .arg("--playlist-items")
+                    .arg(range.to_items_string(self.max_playlist_entries))
+                    .arg(Self::channel_videos(link)),
+            )
+            .await?;
+        let json = String::from_utf8(output.stdout)?.trim().to_string();
+        let dump = serde_json::from_str::<PlaylistDump>(&json)?;
+        Ok(dump.entries)
+    }
+
+    /// Searches YouTube for `query`, returning up to `limit` results without resolving them.
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        _slot: &Slot<'_>,
+    ) -> anyhow::Result<Vec<SearchResult>> {
+        let output = self
+            .output(
+                Command::new(&self.binary_path)
+                    .arg("--flat-playlist")
+                    .arg("--dump-single-json")
+                    .arg(format!("ytsearch{limit}:{query}")),
+            )
+            .await?;
+        let dump = serde_json::from_slice::<SearchDump>(&output.stdout)?;
+        Ok(dump.entries.into_iter().map(SearchResult::from).collect())
+    }
+
+    /// Whether the link points at a YouTube playlist or channel rather than a single video.
+    /// Watch links that merely carry a `list=` parameter are treated as single videos, and
+    /// so is every link to another site, where e.g. `/@name/video/123` is a single video.
+    pub fn is_playlist(link: &str) -> bool {
+        let Ok(url) = Url::parse(link) else {
+            return false;
+        };
+        let is_youtube = matches!(
+            url.host_str(),
+            Some("youtube.com" | "www.youtube.com" | "m.youtube.com" | "music.youtube.com")
+        );
+        if !is_youtube || url.query_pairs().any(|(k, _)| k == "v") {
+            return false;
+        }
+        let path = url.path();
+        url.query_pairs().any(|(k, _)| k == "list")
+            || ["/playlist", "/@", "/channel/", "/c/", "/user/"]
+                .iter()
+                .any(|prefix| path.starts_with(prefix))
+    }
+
+    /// Channel roots flat-extract to their tabs (videos, shorts, live) rather than to
+    /// videos, so they are listed through their videos tab instead.
+    fn channel_videos(link: &str) -> String {
+        let Ok(mut url) = Url::parse(link) else {
+            return link.into();
+        };
+        let segments: Vec<&str> = url
+            .path_segments()
+            .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+        let is_root = match segments[..] {
+            [handle] => handle.starts_with('@'),
+            [kind, _] => ["channel", "c", "user"].contains(&kind),
+            _ => false,
+        };
+        if !is_root {
+            return link.into();
+        }
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().push("videos");
+        }
+        url.into()
+    }
+
+    /// The YouTube video id in a watch, short, embed or youtu.be link.
+    pub fn video_id(link: &str) -> Option<String> {
+        let url = Url::parse(link).ok()?;
+        let mut segments = url.path_segments()?;
+        let id = if url.host_str() == Some("youtu.be") {
+            segments.next().map(String::from)
+        } else if let Some((_, v)) = url.query_pairs().find(|(k, _)| k == "v") {
+            Some(v.into_owned())
+        } else {
+            match segments.next() {
+                Some("shorts" | "embed" | "live") => segments.next().map(String::from),
+                _ => None,
+            }
+        };
+        id.filter(|id| !id.is_empty())
+    }
+
+    /// Downloads the subtitles to `dir`, named after `name`, and returns the file they
+    /// were written to.
+    pub async fn download_subtitles(
+        &self,
+        link: &str,
+        subtitles: &SubtitleInfo,
+        dir: &Path,
+        name: &str,
+        _slot: &Slot<'_>,
+    ) -> anyhow::Result<PathBuf> {
+        tokio::fs::create_dir_all(dir).await.map_err(|e| {
+            anyhow::anyhow!("failed to create subtitles dir {}: {e}", dir.display())
+        })?;
+        let base = dir.join(name);
+        self.output(
+            Command::new(&self.binary_path)
+                .arg("--skip-download")
+                .arg(if subtitles.auto {
+                    "--write-auto-subs"
+                } else {
+                    "--write-subs"
+                })
+                .arg("--sub-langs")
+                .arg(&subtitles.lang)
+                .arg("--sub-format")
+                .arg("vtt/srt/best")
+                .arg("-o")
+                .arg(&base)
+                .arg(link),
+        )
+        .await?;
+
+        // yt-dlp appends the language and the format
+        let pattern = format!("{}.{}.*", base.display(), subtitles.lang);
+        glob(&pattern)?
+            .flatten()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("yt-dlp wrote no {} subtitles", subtitles.lang))
+    }
+
+    /// Downloads the video to `file`, calling `on_progress` whenever yt-dlp reports
+    /// progress. yt-dlp is killed if the returned future is dropped or the download takes
+    /// longer than `download_timeout`.
     pub async fn download_file(
-        temp_file: &NamedTempFile,
+        &self,
+        file: &Path,
         link: &str,
         min_height: MinHeight,
+        _slot: &Slot<'_>,
+        mut on_progress: impl FnMut(DownloadProgress),
     ) -> anyhow::Result<()> {
         info!("starting download {link}");
-        let exit_staus = Command::new("yt-dlp")
+        let mut child = Command::new(&self.binary_path)
             .arg("-f")
             .arg(Format::Split.get_format_string(min_height))
             .arg("--retries")
@@ -57,48 +330,236 @@ impl Video {
             .arg("--fragment-retries")
             .arg("0")
             .arg("--abort-on-unavailable-fragments")
+            .arg("--newline")
+            .arg("--progress")
+            .arg("--progress-template")
+            .arg(PROGRESS_TEMPLATE)
             .arg("-o")
-            .arg(temp_file.as_ref())
+            .arg(file)
             .arg(link)
-            .spawn()?
-            .wait()
-            .await?;
-
-        if !exit_staus.success() {
-            return Err(anyhow::anyhow!("failed to download {}", link));
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        // read alongside stdout so that neither pipe fills up
+        let stderr = child.stderr.take().map(|mut stderr| {
+            tokio::spawn(async move {
+                let mut output = String::new();
+                let _ = stderr.read_to_string(&mut output).await;
+                output
+            })
+        });
+
+        let stdout = child.stdout.take();
+        let run = async {
+            if let Some(stdout) = stdout {
+                let mut lines = BufReader::new(stdout).lines();
+                while let Some(line) = lines.next_line().await? {
+                    if let Some(progress) = DownloadProgress::parse(&line) {
+                        on_progress(progress);
+                    }
+                }
+            }
+            child.wait().await
+        };
+        // the child is dropped and so killed when the timeout hits
+        let exit_status = timeout(self.download_timeout, run)
+            .await
+            .map_err(|_| YtDlpError::timed_out(self.download_timeout))??;
+
+        if !exit_status.success() {
+            let stderr = match stderr {
+                Some(task) => task.await.unwrap_or_default(),
+                None => String::new(),
+            };
+            return Err(YtDlpError::new(exit_status, &stderr).into());
         }
 
         info!("download success {link}");
 
-        info!(
-            "moving file to correct path -> {}",
-            temp_file.as_ref().display()
-        );
+        info!("moving file to correct path -> {}", file.display());
 
-        let pattern = format!("{}.*", temp_file.as_ref().display());
-
-        let paths = glob(&pattern)?;
+        // yt-dlp appends the extension of the format it picked
+        let pattern = format!("{}.*", file.display());
         let mut path = None;
-        for p in paths {
+        for p in glob(&pattern)? {
             match p {
                 Ok(p) => path = Some(p),
                 Err(e) => error!("glob error: {e}"),
             }
         }
-
-        match std::fs::rename(path.unwrap(), temp_file.as_ref()) {
-            Ok(_) => {}
-            Err(e) => {
-                error!("failed to rename file: {e}");
-                return Err(anyhow::anyhow!("failed to rename file: {e}"));
-            }
-        };
+        let path = path.ok_or_else(|| YtDlpError {
+            code: ErrorCode::Failed,
+            message: format!("yt-dlp wrote no file for {}", file.display()),
+        })?;
+
+        if let Err(e) = tokio::fs::rename(&path, file).await {
+            error!("failed to rename file: {e}");
+            return Err(anyhow::anyhow!("failed to rename file: {e}"));
+        }
 
         Ok(())
     }
 }
 
+/// Why yt-dlp failed, in a form the API can hand on.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// removed, never existed or otherwise gone
+    Unavailable,
+    Private,
+    /// age restricted, members only or behind a bot check
+    SignInRequired,
+    GeoBlocked,
+    /// nothing matches the format for the requested height
+    NoMatchingFormat,
+    UnsupportedUrl,
+    Timeout,
+    /// anything that is not recognized
+    Failed,
+}
+
+/// A failed yt-dlp run, classified by what it printed to stderr.
 #[derive(Debug)]
+pub struct YtDlpError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl YtDlpError {
+    // checked in order, private videos also ask to sign in
+    const PATTERNS: &[(&str, ErrorCode)] = &[
+        ("private video", ErrorCode::Private),
+        ("sign in to confirm", ErrorCode::SignInRequired),
+        ("age-restricted", ErrorCode::SignInRequired),
+        ("members-only", ErrorCode::SignInRequired),
+        ("join this channel", ErrorCode::SignInRequired),
+        (
+            "not made this video available in your country",
+            ErrorCode::GeoBlocked,
+        ),
+        ("not available in your country", ErrorCode::GeoBlocked),
+        ("geo restriction", ErrorCode::GeoBlocked),
+        (
+            "requested format is not available",
+            ErrorCode::NoMatchingFormat,
+        ),
+        ("unsupported url", ErrorCode::UnsupportedUrl),
+        ("timed out", ErrorCode::Timeout),
+        ("video unavailable", ErrorCode::Unavailable),
+        ("has been removed", ErrorCode::Unavailable),
+        ("does not exist", ErrorCode::Unavailable),
+        ("is not available", ErrorCode::Unavailable),
+    ];
+
+    fn new(status: ExitStatus, stderr: &str) -> Self {
+        // yt-dlp prefixes the reason with ERROR:, everything else are warnings and noise
+        let message = stderr
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("ERROR: "))
+            .next_back()
+            .map(String::from)
+            .unwrap_or_else(|| format!("yt-dlp failed ({status}): {}", stderr.trim()));
+        let lowercase = message.to_lowercase();
+        let code = Self::PATTERNS
+            .iter()
+            .find(|(pattern, _)| lowercase.contains(pattern))
+            .map(|(_, code)| *code)
+            .unwrap_or(ErrorCode::Failed);
+        Self { code, message }
+    }
+
+    fn timed_out(limit: Duration) -> Self {
+        Self {
+            code: ErrorCode::Timeout,
+            message: format!("yt-dlp did not finish within {}s", limit.as_secs()),
+        }
+    }
+
+    fn check(output: &Output) -> Result<(), Self> {
+        if output.status.success() {
+            return Ok(());
+        }
+        Err(Self::new(
+            output.status,
+            &String::from_utf8_lossy(&output.stderr),
+        ))
+    }
+
+    /// The code of the yt-dlp error behind `error`, if that is what it is.
+    pub fn code_of(error: &anyhow::Error) -> Option<ErrorCode> {
+        error.downcast_ref::<Self>().map(|e| e.code)
+    }
+}
+
+impl fmt::Display for YtDlpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for YtDlpError {}
+
+/// Deletes what an interrupted download of `file` left behind.
+pub async fn remove_partial_download(file: &Path) {
+    let pattern = format!("{}*", file.display());
+    let Ok(paths) = glob(&pattern) else {
+        return;
+    };
+    for path in paths.flatten() {
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            warn!("failed to remove {}: {e}", path.display());
+        }
+    }
+}
+
+// fields are separated by spaces, yt-dlp prints NA for unknown values
+const PROGRESS_TEMPLATE: &str = "download:[progress] %(progress.downloaded_bytes)s \
+%(progress.total_bytes)s %(progress.total_bytes_estimate)s %(progress.speed)s %(progress.eta)s";
+
+/// Progress of a download as reported by yt-dlp. Split formats are downloaded one after
+/// another, so the progress starts over for the audio.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DownloadProgress {
+    pub percent: Option<f64>,
+    pub downloaded_bytes: u64,
+    /// exact if yt-dlp knows it, estimated otherwise
+    pub total_bytes: Option<u64>,
+    /// bytes per second
+    pub speed: Option<u64>,
+    /// seconds
+    pub eta: Option<u64>,
+}
+
+impl DownloadProgress {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line
+            .strip_prefix("[progress] ")?
+            .split_whitespace()
+            .map(|field| field.parse::<f64>().ok().map(|value| value as u64));
+        let downloaded_bytes = fields.next()??;
+        let total_bytes = fields.next()?;
+        let total_bytes_estimate = fields.next()?;
+        let speed = fields.next()?;
+        let eta = fields.next()?;
+
+        let total_bytes = total_bytes
+            .or(total_bytes_estimate)
+            .filter(|total| *total > 0);
+        Some(Self {
+            percent: total_bytes
+                .map(|total| (downloaded_bytes as f64 * 100.0 / total as f64).min(100.0)),
+            downloaded_bytes,
+            total_bytes,
+            speed,
+            eta,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct MergedTrack {
     pub merged_url: String,
     pub track_info: TrackInfo,
@@ -108,9 +569,13 @@ impl TryFrom<JsonDump> for MergedTrack {
     type Error = anyhow::Error;
 
     fn try_from(value: JsonDump) -> Result<Self, Self::Error> {
+        let subtitles = value.subtitle_infos();
+        let chapters = value.chapters();
+        let segments = value.segments();
         match value.url {
             Some(merged_url) => {
                 let track_info = TrackInfo {
+                    id: value.id,
                     title: value.title,
                     channel: value.channel,
                     uploader_id: value.uploader_id,
@@ -123,6 +588,9 @@ impl TryFrom<JsonDump> for MergedTrack {
                     format_id: value.format_id,
                     duration: value.duration,
                     webpage_url: value.webpage_url,
+                    subtitles,
+                    chapters,
+                    segments,
                 };
 
                 Ok(Self {
@@ -137,7 +605,7 @@ impl TryFrom<JsonDump> for MergedTrack {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct SplitTrack {
     pub audio_url: String,
     pub video_url: String,
@@ -148,6 +616,9 @@ impl TryFrom<JsonDump> for SplitTrack {
     type Error = anyhow::Error;
 
     fn try_from(value: JsonDump) -> Result<Self, Self::Error> {
+        let subtitles = value.subtitle_infos();
+        let chapters = value.chapters();
+        let segments = value.segments();
         match value.requested_formats {
             Some(requested_formats) => {
                 if requested_formats.len() != 2 {
@@ -189,6 +660,7 @@ impl TryFrom<JsonDump> for SplitTrack {
                 let video_url = video_url.ok_or_else(|| anyhow::anyhow!("missing video format"))?;
 
                 let track_info = TrackInfo {
+                    id: value.id,
                     title: value.title,
                     channel: value.channel,
                     uploader_id: value.uploader_id,
@@ -201,6 +673,9 @@ impl TryFrom<JsonDump> for SplitTrack {
                     format_id: value.format_id,
                     duration: value.duration,
                     webpage_url: value.webpage_url,
+                    subtitles,
+                    chapters,
+                    segments,
                 };
 
                 Ok(SplitTrack {
@@ -216,8 +691,8 @@ impl TryFrom<JsonDump> for SplitTrack {
     }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-enum TrackType {
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub enum TrackType {
     #[serde(rename = "merged")]
     Merged,
     #[serde(rename = "split")]
@@ -226,6 +701,8 @@ enum TrackType {
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct TrackInfo {
+    #[serde(default)]
+    pub id: String,
     pub title: String,
     channel: String,
     uploader_id: String,
@@ -236,18 +713,172 @@ pub struct TrackInfo {
     thumbnail: String,
     track_type: TrackType,
     pub format_id: String,
-    duration: u32,
+    pub duration: u32,
+    pub webpage_url: String,
+    /// languages the video has subtitles or automatic captions in
+    #[serde(default)]
+    pub subtitles: Vec<SubtitleInfo>,
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+    /// SponsorBlock segments, only looked up if enabled
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+}
+
+/// A section of a video as marked by the uploader, times in seconds.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Chapter {
+    pub title: String,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// A SponsorBlock segment, e.g. a sponsor read, times in seconds.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub category: String,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// Subtitles of a video in one language.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct SubtitleInfo {
+    /// the language as yt-dlp names it, e.g. `en`, `en-US` or `en-orig`
+    pub lang: String,
+    pub name: Option<String>,
+    /// generated by speech recognition rather than written by someone
+    pub auto: bool,
+}
+
+impl SubtitleInfo {
+    /// Whether these are subtitles in `language`, `en` also matches `en-US`.
+    pub fn matches(&self, language: &str) -> bool {
+        self.lang
+            .strip_prefix(language)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+    }
+}
+
+/// 1-based, inclusive range of playlist items; a missing end means "until the last item".
+#[derive(Clone, Copy, Default)]
+pub struct PlaylistRange {
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+}
+
+impl PlaylistRange {
+    /// The range as `--playlist-items`, cut off after `max` entries.
+    fn to_items_string(self, max: usize) -> String {
+        let start = self.start.unwrap_or(1);
+        let last = start.saturating_add(max - 1);
+        let end = self.end.map_or(last, |end| end.min(last));
+        format!("{start}:{end}")
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PlaylistEntry {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PlaylistDump {
+    #[serde(default)]
+    entries: Vec<PlaylistEntry>,
+}
+
+/// A search hit, with the subset of `TrackInfo` that flat extraction knows about.
+#[derive(Serialize, Clone, Debug)]
+pub struct SearchResult {
+    pub title: String,
+    pub channel: String,
+    /// `None` for live streams
+    pub duration: Option<u32>,
+    pub thumbnail: String,
     pub webpage_url: String,
 }
 
+#[derive(Deserialize)]
+struct SearchDump {
+    #[serde(default)]
+    entries: Vec<SearchEntry>,
+}
+
+#[derive(Deserialize)]
+struct SearchEntry {
+    url: String,
+    title: String,
+    #[serde(default)]
+    channel: Option<String>,
+    duration: Option<f64>,
+    // flat entries only list thumbnails, smallest first
+    #[serde(default)]
+    thumbnails: Vec<Thumbnail>,
+}
+
+#[derive(Deserialize)]
+struct Thumbnail {
+    url: String,
+}
+
+impl From<SearchEntry> for SearchResult {
+    fn from(entry: SearchEntry) -> Self {
+        Self {
+            title: entry.title,
+            channel: entry.channel.unwrap_or_default(),
+            duration: entry.duration.map(|d| d.round() as u32),
+            thumbnail: entry
+                .thumbnails
+                .into_iter()
+                .next_back()
+                .map(|t| t.url)
+                .unwrap_or_default(),
+            webpage_url: entry.url,
+        }
+    }
+}
+
+impl TrackInfo {
+    /// Stand-in shown while a job is still being resolved.
+    pub fn pending(webpage_url: &str, title: Option<String>, track_type: TrackType) -> Self {
+        Self {
+            id: String::new(),
+            title: title.unwrap_or_else(|| webpage_url.to_string()),
+            channel: String::new(),
+            uploader_id: String::new(),
+            acodec: String::new(),
+            vcodec: String::new(),
+            height: None,
+            width: None,
+            thumbnail: String::new(),
+            track_type,
+            format_id: String::new(),
+            duration: 0,
+            webpage_url: webpage_url.to_string(),
+            subtitles: vec![],
+            chapters: vec![],
+            segments: vec![],
+        }
+    }
+}
+
 pub enum Track<'a> {
     Merged(MergedTrack),
     Split(SplitTrack),
     File(&'a PathBuf),
+    /// a page URL for players that run yt-dlp themselves, with the format to pick
+    Url {
+        url: String,
+        format: String,
+    },
 }
 
 #[derive(Deserialize)]
 struct JsonDump {
+    #[serde(default)]
+    id: String,
     title: String,
     requested_formats: Option<Vec<RequestedFormat>>,
     url: Option<String>,
@@ -264,6 +895,95 @@ struct JsonDump {
     format_id: String,
     // original link of video
     webpage_url: String,
+    // language -> formats
+    #[serde(default)]
+    subtitles: HashMap<String, Vec<SubtitleFormat>>,
+    #[serde(default)]
+    automatic_captions: HashMap<String, Vec<SubtitleFormat>>,
+    // null rather than empty for videos without chapters
+    chapters: Option<Vec<ChapterDump>>,
+    // only there with --sponsorblock-mark
+    sponsorblock_chapters: Option<Vec<SegmentDump>>,
+}
+
+#[derive(Deserialize)]
+struct SegmentDump {
+    category: String,
+    start_time: f64,
+    end_time: f64,
+}
+
+#[derive(Deserialize)]
+struct ChapterDump {
+    title: Option<String>,
+    start_time: f64,
+    end_time: f64,
+}
+
+#[derive(Deserialize)]
+struct SubtitleFormat {
+    name: Option<String>,
+}
+
+impl JsonDump {
+    fn subtitle_infos(&self) -> Vec<SubtitleInfo> {
+        let info = |auto: bool| {
+            move |(lang, formats): (&String, &Vec<SubtitleFormat>)| SubtitleInfo {
+                lang: lang.clone(),
+                name: formats.iter().find_map(|format| format.name.clone()),
+                auto,
+            }
+        };
+        let mut subtitles: Vec<_> = self
+            .subtitles
+            .iter()
+            // live chat replays are listed as subtitles
+            .filter(|(lang, _)| *lang != "live_chat")
+            .map(info(false))
+            .collect();
+        // captions are also machine translated into every language, only the original
+        // ones are worth listing
+        let mut captions: Vec<_> = self
+            .automatic_captions
+            .iter()
+            .filter(|(lang, _)| lang.ends_with("-orig"))
+            .map(info(true))
+            .collect();
+        subtitles.sort_by(|a, b| a.lang.cmp(&b.lang));
+        captions.sort_by(|a, b| a.lang.cmp(&b.lang));
+        subtitles.extend(captions);
+        subtitles
+    }
+
+    fn chapters(&self) -> Vec<Chapter> {
+        let chapters = self.chapters.as_deref().unwrap_or_default();
+        chapters
+            .iter()
+            .enumerate()
+            .map(|(i, chapter)| Chapter {
+                title: chapter
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| format!("Chapter {}", i + 1)),
+                start: chapter.start_time as u32,
+                end: chapter.end_time as u32,
+            })
+            .collect()
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        let segments = self.sponsorblock_chapters.as_deref().unwrap_or_default();
+        segments
+            .iter()
+            .map(|segment| Segment {
+                category: segment.category.clone(),
+                start: segment.start_time as u32,
+                // rounded up so that skipping lands past the segment
+                end: segment.end_time.ceil() as u32,
+            })
+            .filter(|segment| segment.end > segment.start)
+            .collect()
+    }
 }
 
 #[derive(Deserialize)]
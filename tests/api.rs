@@ -0,0 +1,758 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{TestApp, video};
+use remote_yt::job::JobStatus;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+
+fn job_ids(jobs: &Value) -> Vec<u64> {
+    jobs.as_array()
+        .unwrap()
+        .iter()
+        .map(|job| job["job_id"].as_u64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn queued_stream_plays_and_shows_up_in_history() {
+    let app = TestApp::start().await;
+
+    let response = app
+        .post_json("/api/queue_merged", json!({ "url": video("a", 1) }))
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+    assert!(response.get("batch_id").is_none());
+
+    app.wait_for_status(job_id, JobStatus::Completed).await;
+
+    let job = app.get_json(&format!("/api/jobs/{job_id}")).await;
+    assert_eq!(job["status"]["state"], "completed");
+    let inspect = app.get_json("/api/inspect").await;
+    assert!(inspect["now_playing"].is_null());
+    assert_eq!(job_ids(&inspect["finished"]), [job_id as u64]);
+
+    let history = app.get_json("/api/history").await;
+    assert_eq!(history[0]["title"], "Title a");
+
+    let response = app
+        .post(
+            "/api/remove_history",
+            json!({ "webpage_url": history[0]["webpage_url"] }),
+        )
+        .await;
+    assert!(response.status().is_success());
+    assert_eq!(app.get_json("/api/history").await, json!([]));
+}
+
+#[tokio::test]
+async fn queued_file_is_downloaded_before_playing() {
+    let app = TestApp::start().await;
+
+    let response = app
+        .post_json("/api/queue_file", json!({ "url": video("file", 1) }))
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+
+    app.wait_for_status(job_id, JobStatus::Completed).await;
+    let history = app.get_json("/api/history").await;
+    assert_eq!(history[0]["title"], "Title file");
+}
+
+fn cached_names(cache: &Value) -> Vec<&str> {
+    cache["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn downloads_are_cached_reused_and_evicted() {
+    // each download of the fake is a few dozen bytes, so this fits one
+    let app = TestApp::with_config(|config| config.cache.max_bytes = 60).await;
+
+    for id in ["a", "a", "b"] {
+        let response = app
+            .post_json("/api/queue_file", json!({ "url": video(id, 1) }))
+            .await;
+        let job_id = response["job_id"].as_u64().unwrap() as usize;
+        app.wait_for_status(job_id, JobStatus::Completed).await;
+        let cache = app.get_json("/api/cache").await;
+        assert_eq!(cached_names(&cache), [format!("{id}-480p")]);
+    }
+    assert!(!app.dir.path().join("cache/a-480p").exists());
+
+    let response = app
+        .client
+        .delete(format!("{}/api/cache", app.url))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["purged"], 1);
+    assert_eq!(app.get_json("/api/cache").await["total_bytes"], 0);
+}
+
+#[tokio::test]
+async fn cancelling_a_downloaded_job_deletes_its_file() {
+    let app = TestApp::start().await;
+
+    let response = app
+        .post_json("/api/queue_merged", json!({ "url": video("long", 60) }))
+        .await;
+    let running = response["job_id"].as_u64().unwrap() as usize;
+    app.wait_for_status(running, JobStatus::Playing).await;
+    let response = app
+        .post_json("/api/queue_file", json!({ "url": video("file", 60) }))
+        .await;
+    let queued = response["job_id"].as_u64().unwrap() as usize;
+    app.wait_for_status(queued, JobStatus::Ready).await;
+    assert!(app.dir.path().join("cache/file-480p").exists());
+
+    // still needed by the queued job
+    let response = app
+        .client
+        .delete(format!("{}/api/cache/file-480p", app.url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    app.post(&format!("/api/cancel/{queued}"), json!(null))
+        .await;
+    app.wait_for_status(queued, JobStatus::Cancelled).await;
+    assert!(!app.dir.path().join("cache/file-480p").exists());
+    assert_eq!(
+        cached_names(&app.get_json("/api/cache").await),
+        Vec::<&str>::new()
+    );
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn jobs_for_the_same_file_share_one_download() {
+    let app = TestApp::start().await;
+
+    let response = app
+        .post_json("/api/queue_merged", json!({ "url": video("long", 60) }))
+        .await;
+    let running = response["job_id"].as_u64().unwrap() as usize;
+    app.wait_for_status(running, JobStatus::Playing).await;
+
+    let url = format!("{}&download_secs=2", video("same", 60));
+    let mut jobs = vec![];
+    for _ in 0..3 {
+        let response = app
+            .post_json("/api/queue_file", json!({ "url": url }))
+            .await;
+        jobs.push(response["job_id"].as_u64().unwrap() as usize);
+    }
+    // only one of them downloads, the others wait for it
+    let downloading = app
+        .wait_for(|| async {
+            let mut downloading = vec![];
+            for &job_id in &jobs {
+                let job = app.get_json(&format!("/api/jobs/{job_id}")).await;
+                if !job["progress"].is_null() {
+                    downloading.push(job_id);
+                }
+            }
+            assert!(downloading.len() <= 1, "{downloading:?} download at once");
+            downloading.pop()
+        })
+        .await;
+
+    // another one downloads the file once the first one gives up on it
+    app.post(&format!("/api/cancel/{downloading}"), json!(null))
+        .await;
+    app.wait_for_status(downloading, JobStatus::Cancelled).await;
+    for job_id in jobs.into_iter().filter(|&job_id| job_id != downloading) {
+        app.wait_for_status(job_id, JobStatus::Ready).await;
+    }
+    assert!(app.dir.path().join("cache/same-480p").exists());
+    let cache_dir = std::fs::read_dir(app.dir.path().join("cache")).unwrap();
+    assert_eq!(cache_dir.count(), 1);
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn download_progress_is_reported_and_downloads_can_be_cancelled() {
+    let app = TestApp::start().await;
+
+    let url = format!("{}&download_secs=3", video("slow", 1));
+    let response = app
+        .post_json("/api/queue_file", json!({ "url": url }))
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+
+    let progress = app
+        .wait_for(|| async {
+            let job = app.get_json(&format!("/api/jobs/{job_id}")).await;
+            (!job["progress"].is_null()).then(|| job["progress"].clone())
+        })
+        .await;
+    assert_eq!(progress["total_bytes"], 1000);
+    assert!(progress["percent"].as_f64().unwrap() > 0.0);
+
+    app.post(&format!("/api/cancel/{job_id}"), json!(null))
+        .await;
+    app.wait_for_status(job_id, JobStatus::Cancelled).await;
+    // yt-dlp would have written the file by now if it was still running
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let cache_dir = std::fs::read_dir(app.dir.path().join("cache")).unwrap();
+    assert_eq!(cache_dir.count(), 0);
+}
+
+#[tokio::test]
+async fn download_without_a_file_fails_the_job() {
+    let app = TestApp::start().await;
+
+    let url = format!("{}&nofile=1", video("empty", 1));
+    let response = app
+        .post_json("/api/queue_file", json!({ "url": url }))
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+
+    let job = app.wait_for_finished(job_id).await;
+    let JobStatus::Failed { reason, .. } = job.status else {
+        panic!("expected job to fail, got {:?}", job.status);
+    };
+    assert!(reason.contains("wrote no file"), "{reason}");
+}
+
+#[tokio::test]
+async fn clips_come_from_the_payload_or_the_url_timestamps() {
+    let app = TestApp::start().await;
+
+    let response = app
+        .post(
+            "/api/queue_merged",
+            json!({ "url": video("backwards", 60), "start": 10, "end": 5 }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = app
+        .post_json(
+            "/api/queue_merged",
+            json!({ "url": format!("{}&t=1m55s", video("tail", 120)) }),
+        )
+        .await;
+    let tail = response["job_id"].as_u64().unwrap() as usize;
+    // the fake player plays in real time, so this only finishes in time if the clip is used
+    let response = app
+        .post_json(
+            "/api/queue_merged",
+            json!({ "url": format!("{}&t=30", video("part", 60)), "start": 2, "end": 4 }),
+        )
+        .await;
+    let part = response["job_id"].as_u64().unwrap() as usize;
+
+    let job = app.get_json(&format!("/api/jobs/{tail}")).await;
+    assert_eq!(job["clip"], json!({ "start": 115, "end": null }));
+    let job = app.get_json(&format!("/api/jobs/{part}")).await;
+    assert_eq!(job["clip"], json!({ "start": 2, "end": 4 }));
+
+    app.wait_for_status(tail, JobStatus::Completed).await;
+    app.wait_for_status(part, JobStatus::Completed).await;
+}
+
+#[tokio::test]
+async fn partially_watched_videos_can_be_resumed() {
+    let app = TestApp::with_config(|config| {
+        config.resume.save_interval_secs = 1;
+        config.resume.min_position_secs = 2;
+    })
+    .await;
+
+    let response = app
+        .post_json("/api/queue_merged", json!({ "url": video("resume", 300) }))
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+    app.wait_for_status(job_id, JobStatus::Playing).await;
+    // saved while playing, before the job is stopped
+    let saved = app
+        .wait_for(|| async {
+            let history = app.get_json("/api/history").await;
+            history[0]["position"].as_u64()
+        })
+        .await;
+    assert!(saved >= 2, "{saved}");
+
+    let response = app.post("/api/cancel", json!(null)).await;
+    assert!(response.status().is_success());
+    app.wait_for_status(job_id, JobStatus::Cancelled).await;
+    let history = app.get_json("/api/history").await;
+    let position = history[0]["position"].as_u64().unwrap();
+    assert!(position >= saved, "{position}");
+
+    // without asking to resume the video starts over
+    let url = "https://youtu.be/resume?duration=300";
+    let response = app
+        .post_json("/api/queue_merged", json!({ "url": url }))
+        .await;
+    let job = app
+        .get_json(&format!("/api/jobs/{}", response["job_id"]))
+        .await;
+    assert!(job["clip"].is_null());
+    let response = app
+        .post_json("/api/queue_merged", json!({ "url": url, "resume": true }))
+        .await;
+    let job = app
+        .get_json(&format!("/api/jobs/{}", response["job_id"]))
+        .await;
+    assert_eq!(job["clip"], json!({ "start": position, "end": null }));
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn subtitles_are_picked_by_language_and_downloaded_for_playback() {
+    let app = TestApp::with_config(|config| {
+        config.subtitles.languages = vec!["fr".into(), "en".into()];
+    })
+    .await;
+    let subtitles = |job_id: usize, lang: &str| {
+        app.dir
+            .path()
+            .join(format!("subtitles/job{job_id}.{lang}.vtt"))
+    };
+
+    let url = format!("{}&subs=de,en", video("subs", 60));
+    let response = app
+        .post_json("/api/queue_merged", json!({ "url": url }))
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+    let job = app.wait_for_status(job_id, JobStatus::Playing).await;
+    let langs: Vec<_> = job
+        .track_info
+        .subtitles
+        .iter()
+        .map(|subtitles| (subtitles.lang.as_str(), subtitles.auto))
+        .collect();
+    assert_eq!(langs, [("de", false), ("en", false), ("en-orig", true)]);
+    // downloaded right before the player starts
+    let file = subtitles(job_id, "en");
+    let contents = app
+        .wait_for(|| async { std::fs::read_to_string(&file).ok() })
+        .await;
+    assert_eq!(
+        contents,
+        "WEBVTT\n\n00:00.000 --> 00:01.000\nen subtitles\n"
+    );
+
+    app.wait_for(|| async {
+        let player = app.get_json("/api/inspect").await["player"].clone();
+        (player["state"] == "playing").then_some(())
+    })
+    .await;
+    app.post_json("/api/execute_command", json!("CycleSubtitles"))
+        .await;
+    app.post_json("/api/execute_command", json!("DisableSubtitles"))
+        .await;
+
+    let response = app.post("/api/cancel", json!(null)).await;
+    assert!(response.status().is_success());
+    app.wait_for_status(job_id, JobStatus::Cancelled).await;
+    app.wait_for(|| async { (!file.exists()).then_some(()) })
+        .await;
+
+    // without subtitles in the language the automatic captions are used
+    let response = app
+        .post_json(
+            "/api/queue_merged",
+            json!({ "url": video("captions", 60), "subtitles": "en" }),
+        )
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+    let file = subtitles(job_id, "en-orig");
+    app.wait_for(|| async { file.exists().then_some(()) }).await;
+    let response = app.post("/api/cancel", json!(null)).await;
+    assert!(response.status().is_success());
+
+    let response = app
+        .post_json(
+            "/api/queue_merged",
+            json!({ "url": video("none", 45), "subtitles": "" }),
+        )
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+    app.wait_for_status(job_id, JobStatus::Playing).await;
+    app.wait_for(|| async {
+        let player = app.get_json("/api/inspect").await["player"].clone();
+        // the player of this job has started
+        (player["length"] == 45).then_some(())
+    })
+    .await;
+    let files = std::fs::read_dir(app.dir.path().join("subtitles")).unwrap();
+    assert_eq!(files.count(), 0);
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn playlist_is_queued_as_batch_and_cancelled_together() {
+    let app = TestApp::start().await;
+
+    let url = format!(
+        "{}&count=3",
+        video("list", 60).replace("watch?v=", "playlist?list=")
+    );
+    let response = app
+        .post_json("/api/queue_playlist", json!({ "url": url }))
+        .await;
+    let batch_id = response["batch_id"].as_u64().unwrap();
+    let job_ids = response["job_ids"].as_array().unwrap();
+    assert_eq!(job_ids.len(), 3);
+
+    let first = job_ids[0].as_u64().unwrap() as usize;
+    app.wait_for_status(first, JobStatus::Playing).await;
+
+    let response = app
+        .post_json(&format!("/api/cancel_batch/{batch_id}"), json!(null))
+        .await;
+    assert_eq!(response["cancelled"], 3);
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn channels_are_expanded_through_their_videos_tab_up_to_the_limit() {
+    let app = TestApp::with_config(|config| config.yt_dlp.max_playlist_entries = 2).await;
+
+    let url = "https://www.youtube.com/@channel?count=5&duration=60";
+    let response = app
+        .post_json("/api/queue_playlist", json!({ "url": url, "start": 2 }))
+        .await;
+    let job_ids = response["job_ids"].as_array().unwrap();
+    assert_eq!(job_ids.len(), 2);
+
+    let first = job_ids[0].as_u64().unwrap() as usize;
+    app.wait_for_status(first, JobStatus::Playing).await;
+    let job = app.get_json(&format!("/api/jobs/{first}")).await;
+    assert_eq!(job["track_info"]["title"], "Title video-2");
+
+    // queueing a channel as a stream expands it the same way
+    let response = app
+        .post_json("/api/queue_merged", json!({ "url": url }))
+        .await;
+    let batch_id = response["batch_id"].as_u64().unwrap();
+    let response = app
+        .post_json(&format!("/api/cancel_batch/{batch_id}"), json!(null))
+        .await;
+    assert_eq!(response["cancelled"], 2);
+
+    for option in [
+        json!({ "start": 10 }),
+        json!({ "end": 10 }),
+        json!({ "resume": true }),
+        json!({ "subtitles": "" }),
+    ] {
+        let mut payload = option.clone();
+        payload["url"] = json!(url);
+        let response = app.post("/api/queue_split", payload).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{option}");
+    }
+
+    for range in [
+        json!({ "start": 0 }),
+        json!({ "end": 0 }),
+        json!({ "start": 3, "end": 2 }),
+    ] {
+        let mut payload = range.clone();
+        payload["url"] = json!(url);
+        let response = app.post("/api/queue_playlist", payload).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{range}");
+    }
+    // far past the end of the playlist, without overflowing the entry limit
+    let response = app
+        .post(
+            "/api/queue_playlist",
+            json!({ "url": url, "start": usize::MAX }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn profile_links_of_other_sites_are_queued_as_single_videos() {
+    let app = TestApp::start().await;
+
+    let url = "https://www.tiktok.com/@name/video/123?duration=60";
+    let response = app
+        .post_json("/api/queue_merged", json!({ "url": url }))
+        .await;
+    assert!(response["batch_id"].is_null());
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+    let job = app.wait_for_status(job_id, JobStatus::Playing).await;
+    assert_eq!(job.track_info.title, "Title video");
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn queue_routes_move_swap_and_cancel_jobs() {
+    let app = TestApp::start().await;
+
+    let mut ids = vec![];
+    for id in ["a", "b", "c"] {
+        let response = app
+            .post_json("/api/queue_merged", json!({ "url": video(id, 60) }))
+            .await;
+        let job_id = response["job_id"].as_u64().unwrap();
+        // jobs are played in the order they become ready
+        let status = if id == "a" {
+            JobStatus::Playing
+        } else {
+            JobStatus::Ready
+        };
+        app.wait_for_status(job_id as usize, status).await;
+        ids.push(job_id);
+    }
+    let [a, b, c] = ids[..] else { unreachable!() };
+
+    app.post_json(&format!("/api/move/{c}/0"), json!(null))
+        .await;
+    let inspect = app.get_json("/api/inspect").await;
+    assert_eq!(inspect["now_playing"]["job_id"], a);
+    assert_eq!(job_ids(&inspect["queue"]), [c, b]);
+
+    app.post_json(&format!("/api/swap/{b}"), json!(null)).await;
+    app.wait_for_status(b as usize, JobStatus::Playing).await;
+    let inspect = app.get_json("/api/inspect").await;
+    assert_eq!(job_ids(&inspect["queue"]), [c, a]);
+
+    let response = app.post(&format!("/api/cancel/{c}"), json!(null)).await;
+    assert_eq!(response.text().await.unwrap(), "task cancelled");
+    let response = app.post(&format!("/api/cancel/{c}"), json!(null)).await;
+    assert_eq!(response.text().await.unwrap(), "not found");
+
+    let response = app.post("/api/clear", json!(null)).await;
+    assert_eq!(response.text().await.unwrap(), "queue cleared");
+    app.wait_for_status(b as usize, JobStatus::Cancelled).await;
+    let inspect = app.get_json("/api/inspect").await;
+    assert!(inspect["now_playing"].is_null());
+    assert_eq!(inspect["queue"], json!([]));
+}
+
+#[tokio::test]
+async fn player_commands_reach_the_player() {
+    let app = TestApp::start().await;
+
+    let response = app
+        .post_json("/api/queue_merged", json!({ "url": video("a", 60) }))
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+    app.wait_for_status(job_id, JobStatus::Playing).await;
+    // the player takes a moment to bring up its interface
+    app.wait_for(|| async {
+        let player = app.get_json("/api/inspect").await["player"].clone();
+        (player["state"] == "playing").then_some(())
+    })
+    .await;
+
+    app.post_json("/api/execute_command", json!("TogglePause"))
+        .await;
+    app.post_json("/api/execute_command", json!("Mute")).await;
+    app.wait_for(|| async {
+        let player = app.get_json("/api/inspect").await["player"].clone();
+        (player["state"] == "paused" && player["volume"] == 0).then_some(())
+    })
+    .await;
+
+    app.post_json("/api/execute_command", json!({ "SeekTo": 30 }))
+        .await;
+    app.wait_for(|| async {
+        let player = app.get_json("/api/inspect").await["player"].clone();
+        (player["time"] == 30).then_some(())
+    })
+    .await;
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn chapters_are_listed_and_navigable() {
+    let app = TestApp::start().await;
+
+    let response = app
+        .post_json(
+            "/api/queue_merged",
+            json!({ "url": format!("{}&chapters=3", video("talk", 60)) }),
+        )
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+    app.wait_for_status(job_id, JobStatus::Playing).await;
+    app.wait_for(|| async {
+        let player = app.get_json("/api/inspect").await["player"].clone();
+        (player["state"] == "playing").then_some(())
+    })
+    .await;
+    let inspect = app.get_json("/api/inspect").await;
+    assert_eq!(
+        inspect["now_playing"]["track_info"]["chapters"][1],
+        json!({ "title": "Part 2", "start": 20, "end": 40 })
+    );
+
+    // paused, so that the position only changes through the commands
+    app.post_json("/api/execute_command", json!("TogglePause"))
+        .await;
+    let seek_to = |command: Value, time: u32| {
+        let app = &app;
+        async move {
+            app.post_json("/api/execute_command", command).await;
+            app.wait_for(|| async {
+                let player = app.get_json("/api/inspect").await["player"].clone();
+                (player["time"] == time).then_some(())
+            })
+            .await;
+        }
+    };
+    seek_to(json!("NextChapter"), 20).await;
+    seek_to(json!({ "SeekToChapter": 2 }), 40).await;
+    // right at the start of a chapter goes back to the one before it
+    seek_to(json!("PreviousChapter"), 20).await;
+    seek_to(json!({ "SeekTo": 30 }), 30).await;
+    seek_to(json!("PreviousChapter"), 20).await;
+
+    let response = app
+        .post("/api/execute_command", json!({ "SeekToChapter": 3 }))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn failed_job_can_be_retried() {
+    let app = TestApp::start().await;
+
+    let url = format!("{}&fail=1", video("gone", 1));
+    let response = app
+        .post_json("/api/queue_merged", json!({ "url": url }))
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+    app.wait_for_finished(job_id).await;
+
+    let job = app.get_json(&format!("/api/jobs/{job_id}")).await;
+    assert_eq!(job["status"]["state"], "failed");
+
+    let response = app
+        .post_json(&format!("/api/jobs/{job_id}/retry"), json!(null))
+        .await;
+    let retried = response["job_id"].as_u64().unwrap() as usize;
+    assert_ne!(retried, job_id);
+    app.wait_for_finished(retried).await;
+}
+
+#[tokio::test]
+async fn search_results_are_cached() {
+    let app = TestApp::start().await;
+
+    let results = app.get_json("/api/search?q=cats&limit=2").await;
+    assert_eq!(results.as_array().unwrap().len(), 2);
+    assert_eq!(results[0]["title"], "cats 1");
+    assert_eq!(results[0]["duration"], 60);
+
+    // the fake stamps every call, so the same thumbnail means the same call
+    let again = app.get_json("/api/search?q=%20Cats&limit=2").await;
+    assert_eq!(again, results);
+    let more = app.get_json("/api/search?q=cats&limit=3").await;
+    assert_eq!(more.as_array().unwrap().len(), 3);
+
+    let response = app.get("/api/search?q=").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn lookups_give_up_while_downloads_take_every_slot() {
+    let app = TestApp::with_config(|config| {
+        config.yt_dlp.max_processes = 1;
+        config.yt_dlp.timeout_secs = 1;
+    })
+    .await;
+
+    let url = format!("{}&download_secs=30", video("download", 60));
+    let response = app
+        .post_json("/api/queue_file", json!({ "url": url }))
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+    app.wait_for_status(job_id, JobStatus::Downloading).await;
+
+    let started = Instant::now();
+    let response = app.get("/api/search?q=cats").await;
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "timeout");
+    let url = video("list", 60).replace("watch?v=", "playlist?list=");
+    let response = app.post("/api/queue_playlist", json!({ "url": url })).await;
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn yt_dlp_errors_are_classified() {
+    let app = TestApp::start().await;
+
+    let playlist = |fail: &str| {
+        format!(
+            "{}&fail={fail}",
+            video("list", 1).replace("watch?v=", "playlist?list=")
+        )
+    };
+    for (fail, status, code) in [
+        ("private", StatusCode::FORBIDDEN, "private"),
+        ("age", StatusCode::FORBIDDEN, "sign_in_required"),
+        (
+            "geo",
+            StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            "geo_blocked",
+        ),
+        ("unsupported", StatusCode::BAD_REQUEST, "unsupported_url"),
+        ("1", StatusCode::NOT_FOUND, "unavailable"),
+    ] {
+        let response = app
+            .post("/api/queue_playlist", json!({ "url": playlist(fail) }))
+            .await;
+        assert_eq!(response.status(), status, "{fail}");
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["code"], code);
+        assert!(!body["error"].as_str().unwrap().contains("WARNING"));
+    }
+
+    // jobs carry the code of the error they failed with
+    let url = format!("{}&fail=format", video("hd", 1));
+    let response = app
+        .post_json("/api/queue_merged", json!({ "url": url }))
+        .await;
+    let job_id = response["job_id"].as_u64().unwrap() as usize;
+    app.wait_for_finished(job_id).await;
+    let job = app.get_json(&format!("/api/jobs/{job_id}")).await;
+    assert_eq!(job["status"]["code"], "no_matching_format");
+}
+
+#[tokio::test]
+async fn unknown_jobs_are_reported_as_errors() {
+    let app = TestApp::start().await;
+
+    let response = app.get("/api/jobs/42").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = app.post("/api/swap/42", json!(null)).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("42"));
+
+    for path in ["/api/move/42/0", "/api/jobs/42/retry"] {
+        let response = app.post(path, json!(null)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{path}");
+    }
+}
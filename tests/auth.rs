@@ -0,0 +1,288 @@
+mod common;
+
+use common::{TestApp, video};
+use remote_yt::{
+    auth::Role,
+    config::{AuthConfig, Scheduling, UserConfig},
+    job::JobStatus,
+};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+
+const ADMIN_PIN: &str = "1234";
+const ALICE: &str = "alice-token";
+const BOB: &str = "bob-token";
+const GUEST_LIMIT: usize = 2;
+
+fn auth_config() -> AuthConfig {
+    AuthConfig {
+        enabled: true,
+        admin_pin: Some(ADMIN_PIN.into()),
+        users: vec![
+            UserConfig {
+                name: "alice".into(),
+                token: ALICE.into(),
+                role: Role::Member,
+            },
+            UserConfig {
+                name: "bob".into(),
+                token: BOB.into(),
+                role: Role::Member,
+            },
+        ],
+        guests: true,
+        guest_max_pending: Some(GUEST_LIMIT),
+    }
+}
+
+async fn start() -> TestApp {
+    TestApp::with_config(|config| config.auth = auth_config()).await
+}
+
+async fn post_as(app: &TestApp, token: &str, path: &str, body: Value) -> reqwest::Response {
+    app.client
+        .post(format!("{}{path}", app.url))
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn queue_as(app: &TestApp, token: &str, url: String) -> usize {
+    let response = post_as(app, token, "/api/queue_merged", json!({ "url": url })).await;
+    assert!(response.status().is_success());
+    let body: Value = response.json().await.unwrap();
+    body["job_id"].as_u64().unwrap() as usize
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_token_are_rejected() {
+    let app = start().await;
+
+    assert_eq!(
+        app.get("/api/inspect").await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    let response = post_as(&app, "wrong", "/api/clear", json!(null)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .client
+        .get(format!("{}/api/me", app.url))
+        .bearer_auth(ALICE)
+        .send()
+        .await
+        .unwrap();
+    let me: Value = response.json().await.unwrap();
+    assert_eq!(me, json!({ "name": "alice", "role": "member" }));
+}
+
+#[tokio::test]
+async fn login_sets_a_cookie_that_identifies_the_caller() {
+    let app = start().await;
+
+    let response = app.post("/api/login", json!({ "token": "wrong" })).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app.post("/api/login", json!({ "token": ADMIN_PIN })).await;
+    assert!(response.status().is_success());
+    let cookie = response.headers()["set-cookie"].to_str().unwrap();
+    let cookie = cookie.split(';').next().unwrap().to_string();
+
+    let response = app
+        .client
+        .get(format!("{}/api/me", app.url))
+        .header("cookie", cookie)
+        .send()
+        .await
+        .unwrap();
+    let me: Value = response.json().await.unwrap();
+    assert_eq!(me, json!({ "name": "admin", "role": "admin" }));
+}
+
+#[tokio::test]
+async fn submitter_is_recorded_on_jobs_and_history() {
+    let app = start().await;
+
+    let job_id = queue_as(&app, ALICE, video("a", 1)).await;
+    let job = app.wait_for_status(job_id, JobStatus::Completed).await;
+    assert_eq!(job.submitter.as_deref(), Some("alice"));
+
+    let history = serde_json::to_value(app.state.queue.get_history().await).unwrap();
+    assert_eq!(history[0]["submitter"], "alice");
+}
+
+#[tokio::test]
+async fn only_owners_and_admins_can_cancel_and_clear() {
+    let app = start().await;
+
+    let running = queue_as(&app, ALICE, video("long", 60)).await;
+    app.wait_for_status(running, JobStatus::Playing).await;
+    let queued = queue_as(&app, ALICE, video("queued", 60)).await;
+
+    let response = post_as(&app, BOB, &format!("/api/cancel/{queued}"), json!(null)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = post_as(&app, BOB, "/api/cancel", json!(null)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = post_as(&app, ALICE, "/api/clear", json!(null)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = post_as(
+        &app,
+        ALICE,
+        "/api/remove_history",
+        json!({ "webpage_url": "x" }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = post_as(&app, ALICE, &format!("/api/cancel/{queued}"), json!(null)).await;
+    assert!(response.status().is_success());
+    app.wait_for_status(queued, JobStatus::Cancelled).await;
+
+    let response = post_as(&app, ADMIN_PIN, "/api/clear", json!(null)).await;
+    assert!(response.status().is_success());
+    app.wait_for_status(running, JobStatus::Cancelled).await;
+}
+
+#[tokio::test]
+async fn members_cannot_swap_or_control_the_player() {
+    let app = start().await;
+
+    let running = queue_as(&app, ALICE, video("long", 60)).await;
+    app.wait_for_status(running, JobStatus::Playing).await;
+    let queued = queue_as(&app, ALICE, video("queued", 60)).await;
+
+    let response = post_as(&app, ALICE, &format!("/api/swap/{queued}"), json!(null)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = post_as(&app, ALICE, "/api/execute_command", json!("Mute")).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = post_as(&app, BOB, &format!("/api/move/{queued}/0"), json!(null)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = post_as(&app, ALICE, &format!("/api/move/{queued}/0"), json!(null)).await;
+    assert!(response.status().is_success());
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn guests_join_by_name_and_can_only_queue_a_few_jobs() {
+    let app = start().await;
+
+    let response = app.post("/api/login", json!({ "name": "alice" })).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response = app.post("/api/login", json!({ "name": "carol" })).await;
+    assert!(response.status().is_success());
+    let cookie = response.headers()["set-cookie"].to_str().unwrap();
+    let token = cookie
+        .split(';')
+        .next()
+        .unwrap()
+        .split_once('=')
+        .unwrap()
+        .1
+        .to_string();
+    let me: Value = response.json().await.unwrap();
+    assert_eq!(me, json!({ "name": "carol", "role": "guest" }));
+
+    let running = queue_as(&app, ADMIN_PIN, video("long", 60)).await;
+    app.wait_for_status(running, JobStatus::Playing).await;
+    let mut queued = vec![];
+    for i in 0..GUEST_LIMIT {
+        queued.push(queue_as(&app, &token, video(&format!("guest{i}"), 60)).await);
+    }
+    let response = post_as(
+        &app,
+        &token,
+        "/api/queue_merged",
+        json!({ "url": video("more", 60) }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let response = post_as(
+        &app,
+        &token,
+        &format!("/api/cancel/{}", queued[0]),
+        json!(null),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = post_as(&app, &token, "/api/clear", json!(null)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn only_admins_can_move_jobs_when_taking_turns() {
+    let app = TestApp::with_config(|config| {
+        config.auth = auth_config();
+        config.scheduling = Scheduling::Fair;
+    })
+    .await;
+
+    let running = queue_as(&app, ADMIN_PIN, video("long", 60)).await;
+    app.wait_for_status(running, JobStatus::Playing).await;
+    let first = queue_as(&app, BOB, video("first", 60)).await;
+    let second = queue_as(&app, ALICE, video("second", 60)).await;
+
+    let response = post_as(&app, ALICE, &format!("/api/move/{second}/0"), json!(null)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = post_as(
+        &app,
+        ADMIN_PIN,
+        &format!("/api/move/{second}/0"),
+        json!(null),
+    )
+    .await;
+    assert!(response.status().is_success());
+    let (_, queue, _) = app.state.queue.inspect().await;
+    let ids: Vec<_> = queue.iter().map(|job| job.job_id).collect();
+    assert_eq!(ids, [second, first]);
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn votes_rank_the_queue_and_enough_skip_votes_skip_the_current_job() {
+    let app = TestApp::with_config(|config| {
+        config.auth = auth_config();
+        config.scheduling = Scheduling::Democratic;
+    })
+    .await;
+
+    let running = queue_as(&app, ADMIN_PIN, video("long", 60)).await;
+    app.wait_for_status(running, JobStatus::Playing).await;
+    let first = queue_as(&app, ALICE, video("first", 60)).await;
+    let second = queue_as(&app, BOB, video("second", 60)).await;
+
+    let response = post_as(
+        &app,
+        BOB,
+        &format!("/api/vote/{second}"),
+        json!({ "vote": "up" }),
+    )
+    .await;
+    assert!(response.status().is_success());
+    let (_, queue, _) = app.state.queue.inspect().await;
+    let ids: Vec<_> = queue.iter().map(|job| job.job_id).collect();
+    assert_eq!(ids, [second, first]);
+    assert_eq!(queue[0].upvotes, 1);
+    let response = post_as(&app, BOB, "/api/vote/42", json!({ "vote": "up" })).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // admin, alice and bob are active, so it takes two of them
+    let response = post_as(&app, ALICE, "/api/vote_skip", json!(null)).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body, json!({ "votes": 1, "needed": 2, "skipped": false }));
+    let response = post_as(&app, ALICE, "/api/vote_skip", json!(null)).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["votes"], 1);
+    let response = post_as(&app, BOB, "/api/vote_skip", json!(null)).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["skipped"], true);
+    app.wait_for_status(running, JobStatus::Cancelled).await;
+
+    app.clear().await;
+}
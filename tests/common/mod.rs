@@ -0,0 +1,167 @@
+// every test binary uses a different subset of the helpers
+#![allow(dead_code)]
+
+use std::{
+    net::TcpListener as StdTcpListener,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use remote_yt::{AppState, api, config::Config, job::JobStatus, meta::InspectMetadata};
+use serde_json::Value;
+use tempfile::TempDir;
+use tokio::{
+    net::TcpListener,
+    time::{Instant, sleep},
+};
+
+const WAIT_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// A server wired to the scripted yt-dlp and vlc in `tests/fakes` (which need python3),
+/// with its state files in a temporary directory.
+pub struct TestApp {
+    pub state: Arc<AppState>,
+    pub url: String,
+    pub client: reqwest::Client,
+    pub dir: TempDir,
+}
+
+pub fn fakes_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fakes")
+}
+
+/// A watch link the fake yt-dlp resolves to a video of `duration` seconds.
+pub fn video(id: &str, duration: u32) -> String {
+    format!("https://www.youtube.com/watch?v={id}&duration={duration}")
+}
+
+fn free_port() -> u16 {
+    StdTcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+impl TestApp {
+    pub async fn start() -> Self {
+        Self::with_config(|_| {}).await
+    }
+
+    pub async fn with_config(configure: impl FnOnce(&mut Config)) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut config = Config {
+            static_dir: dir.path().into(),
+            history_file: dir.path().join("history.json"),
+            queue_file: dir.path().join("queue.json"),
+            ..Default::default()
+        };
+        config.cache.dir = dir.path().join("cache");
+        config.subtitles.dir = dir.path().join("subtitles");
+        config.yt_dlp.binary = fakes_dir().join("yt-dlp");
+        config.vlc.binary = fakes_dir().join("vlc");
+        config.vlc.host = "127.0.0.1".into();
+        config.vlc.port = free_port();
+        config.retry.backoff_secs = 0;
+        configure(&mut config);
+
+        let state = AppState::new(&config).await.unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let router = api::router(state.clone(), &config.static_dir);
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        Self {
+            state,
+            url,
+            client: reqwest::Client::new(),
+            dir,
+        }
+    }
+
+    pub async fn get(&self, path: &str) -> reqwest::Response {
+        self.client
+            .get(format!("{}{path}", self.url))
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post(&self, path: &str, body: Value) -> reqwest::Response {
+        self.client
+            .post(format!("{}{path}", self.url))
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_json(&self, path: &str) -> Value {
+        let response = self.get(path).await;
+        assert!(response.status().is_success(), "GET {path}: {response:?}");
+        response.json().await.unwrap()
+    }
+
+    pub async fn post_json(&self, path: &str, body: Value) -> Value {
+        let response = self.post(path, body).await;
+        assert!(response.status().is_success(), "POST {path}: {response:?}");
+        response.json().await.unwrap()
+    }
+
+    /// Waits until the job has reached `status`.
+    pub async fn wait_for_status(&self, job_id: usize, status: JobStatus) -> InspectMetadata {
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+        loop {
+            let job = self.state.queue.get_job(job_id).await;
+            match job {
+                Some(job) if job.status == status => return job,
+                _ if Instant::now() > deadline => panic!(
+                    "timed out waiting for job {job_id} to be {status:?}, is {:?}",
+                    job.map(|job| job.status)
+                ),
+                _ => sleep(Duration::from_millis(100)).await,
+            }
+        }
+    }
+
+    /// Waits until the job has finished, whatever the outcome.
+    pub async fn wait_for_finished(&self, job_id: usize) -> InspectMetadata {
+        self.wait_for(|| async {
+            self.state
+                .queue
+                .get_job(job_id)
+                .await
+                .filter(|job| job.status.is_finished())
+        })
+        .await
+    }
+
+    /// Clears the queue and waits for the player to be stopped, so that no fake
+    /// player outlives the test.
+    pub async fn clear(&self) {
+        self.state.queue.clear().await;
+        self.wait_for(|| async {
+            let (current, _, _) = self.state.queue.inspect().await;
+            current.is_none().then_some(())
+        })
+        .await
+    }
+
+    /// Polls `check` until it returns something, failing the test after a timeout.
+    pub async fn wait_for<T, F>(&self, check: impl Fn() -> F) -> T
+    where
+        F: Future<Output = Option<T>>,
+    {
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+        loop {
+            if let Some(value) = check().await {
+                return value;
+            }
+            assert!(Instant::now() < deadline, "timed out waiting");
+            sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
@@ -0,0 +1,382 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{TestApp, video};
+use remote_yt::{
+    config::{Scheduling, VlcMode},
+    events::Event,
+    format::MinHeight,
+    job::{JobOptions, JobStatus, JobType},
+    yt_dlp::ErrorCode,
+};
+
+fn merged(url: String) -> JobType {
+    JobType::QueueMerged {
+        url,
+        height: MinHeight(480),
+        format_id: String::new(),
+    }
+}
+
+/// Queues `url` as a merged stream without any options.
+async fn submit(app: &TestApp, url: String) -> usize {
+    app.state
+        .queue
+        .submit(merged(url), JobOptions::default())
+        .await
+}
+
+async fn queue_ids(app: &TestApp) -> Vec<usize> {
+    let (_, queue, _) = app.state.queue.inspect().await;
+    queue.iter().map(|job| job.job_id).collect()
+}
+
+#[tokio::test]
+async fn submitted_job_plays_to_completion_and_is_added_to_history() {
+    let app = TestApp::start().await;
+
+    let job_id = submit(&app, video("a", 1)).await;
+    let job = app.wait_for_status(job_id, JobStatus::Completed).await;
+
+    assert_eq!(job.track_info.title, "Title a");
+    assert!(job.started_at.is_some() && job.finished_at.is_some());
+
+    let history = serde_json::to_value(app.state.queue.get_history().await).unwrap();
+    assert_eq!(history[0]["title"], "Title a");
+    let written = std::fs::read_to_string(app.dir.path().join("history.json")).unwrap();
+    assert!(written.contains("Title a"));
+    assert!(!app.dir.path().join("history.json.tmp").exists());
+}
+
+#[tokio::test]
+async fn failed_resolve_marks_job_failed() {
+    let app = TestApp::start().await;
+
+    let job_id = submit(&app, format!("{}&fail=1", video("gone", 1))).await;
+    let job = app.wait_for_finished(job_id).await;
+
+    let JobStatus::Failed { reason, code } = job.status else {
+        panic!("expected job to fail, got {:?}", job.status);
+    };
+    assert!(reason.contains("Video unavailable"), "{reason}");
+    assert_eq!(code, Some(ErrorCode::Unavailable));
+    assert!(app.state.queue.get_history().await.is_empty());
+}
+
+#[tokio::test]
+async fn crashing_player_is_retried_until_attempts_run_out() {
+    let app = TestApp::with_config(|config| config.retry.max_attempts = 2).await;
+
+    let job_id = submit(&app, video("crash", 10)).await;
+    let job = app.wait_for_finished(job_id).await;
+
+    assert!(
+        matches!(job.status, JobStatus::Failed { .. }),
+        "{:?}",
+        job.status
+    );
+}
+
+#[tokio::test]
+async fn cancel_stops_running_job() {
+    let app = TestApp::start().await;
+    let queue = &app.state.queue;
+
+    let job_id = submit(&app, video("long", 60)).await;
+    app.wait_for_status(job_id, JobStatus::Playing).await;
+
+    assert!(queue.cancel().await);
+    app.wait_for_status(job_id, JobStatus::Cancelled).await;
+    assert!(!queue.cancel().await);
+}
+
+#[tokio::test]
+async fn persistent_vlc_plays_one_track_after_another() {
+    let app = TestApp::with_config(|config| config.vlc.mode = VlcMode::Persistent).await;
+
+    // long enough to be seen playing, the persistent vlc is only polled once a second
+    let first = submit(&app, video("first", 3)).await;
+    let second = submit(&app, video("second", 3)).await;
+    app.wait_for_status(first, JobStatus::Completed).await;
+    let job = app.wait_for_status(second, JobStatus::Completed).await;
+    assert!(job.started_at.is_some() && job.finished_at.is_some());
+}
+
+#[tokio::test]
+async fn persistent_vlc_stops_cancelled_jobs_and_restarts_after_a_crash() {
+    let app = TestApp::with_config(|config| {
+        config.vlc.mode = VlcMode::Persistent;
+        config.retry.max_attempts = 1;
+    })
+    .await;
+
+    let job_id = submit(&app, video("long", 60)).await;
+    app.wait_for_status(job_id, JobStatus::Playing).await;
+    assert!(app.state.queue.cancel().await);
+    app.wait_for_status(job_id, JobStatus::Cancelled).await;
+
+    let job_id = submit(&app, video("crash", 10)).await;
+    let job = app.wait_for_finished(job_id).await;
+    let JobStatus::Failed { reason, .. } = job.status else {
+        panic!("expected job to fail, got {:?}", job.status);
+    };
+    assert!(reason.contains("crashed"), "{reason}");
+
+    // the supervisor brings vlc back for the next job
+    let job_id = submit(&app, video("after", 3)).await;
+    app.wait_for_status(job_id, JobStatus::Completed).await;
+}
+
+#[tokio::test]
+async fn queued_jobs_can_be_reordered_cancelled_and_cleared() {
+    let app = TestApp::start().await;
+    let queue = &app.state.queue;
+
+    let running = submit(&app, video("long", 60)).await;
+    app.wait_for_status(running, JobStatus::Playing).await;
+
+    let first = submit(&app, video("first", 1)).await;
+    let second = submit(&app, video("second", 1)).await;
+    let third = submit(&app, video("third", 1)).await;
+    assert_eq!(queue_ids(&app).await, [first, second, third]);
+
+    queue.reorder_job(third, 0).await.unwrap();
+    assert_eq!(queue_ids(&app).await, [third, first, second]);
+    assert!(queue.reorder_job(running, 0).await.is_err());
+
+    assert!(queue.cancel_by_id(first).await);
+    assert!(!queue.cancel_by_id(first).await);
+    assert_eq!(queue_ids(&app).await, [third, second]);
+
+    app.clear().await;
+    assert!(queue_ids(&app).await.is_empty());
+    for job_id in [running, second, third] {
+        app.wait_for_status(job_id, JobStatus::Cancelled).await;
+    }
+}
+
+#[tokio::test]
+async fn swap_plays_queued_job_and_requeues_running_one() {
+    let app = TestApp::start().await;
+    let queue = &app.state.queue;
+
+    let running = submit(&app, video("long", 60)).await;
+    app.wait_for_status(running, JobStatus::Playing).await;
+    let next = submit(&app, video("next", 60)).await;
+    app.wait_for_status(next, JobStatus::Ready).await;
+
+    queue.swap_with_running(next).await.unwrap();
+
+    app.wait_for_status(next, JobStatus::Playing).await;
+    let requeued = app.wait_for_status(running, JobStatus::Ready).await;
+    assert!(requeued.started_at.is_none());
+    assert_eq!(queue_ids(&app).await, [running]);
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn fair_scheduling_takes_turns_between_submitters() {
+    let app = TestApp::with_config(|config| config.scheduling = Scheduling::Fair).await;
+    let queue = &app.state.queue;
+    let submit = |id: &str, submitter: &str| {
+        let options = JobOptions {
+            submitter: Some(submitter.into()),
+            ..Default::default()
+        };
+        queue.submit(merged(video(id, 60)), options)
+    };
+
+    let running = submit("a0", "a").await;
+    app.wait_for_status(running, JobStatus::Playing).await;
+    let mut a = vec![];
+    for id in ["a1", "a2", "a3"] {
+        a.push(submit(id, "a").await);
+    }
+    let b1 = submit("b1", "b").await;
+    let b2 = submit("b2", "b").await;
+    let c1 = submit("c1", "c").await;
+    assert_eq!(queue_ids(&app).await, [b1, c1, a[0], b2, a[1], a[2]]);
+
+    // moving a job by hand sticks, later jobs are fitted in around it
+    queue.reorder_job(a[2], 0).await.unwrap();
+    let c2 = submit("c2", "c").await;
+    assert_eq!(queue_ids(&app).await, [a[2], b1, c1, a[0], b2, c2, a[1]]);
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn next_job_starts_with_the_streams_resolved_ahead() {
+    let app = TestApp::start().await;
+    let queue = &app.state.queue;
+
+    let running = submit(&app, video("long", 60)).await;
+    app.wait_for_status(running, JobStatus::Playing).await;
+    // every call of the fake yt-dlp takes 3s for this one
+    let next = submit(&app, format!("{}&delay=3", video("next", 60))).await;
+    app.wait_for_status(next, JobStatus::Ready).await;
+
+    let skipped_at = Instant::now();
+    queue.cancel().await;
+    app.wait_for_status(next, JobStatus::Playing).await;
+    assert!(skipped_at.elapsed() < Duration::from_secs(2));
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn queue_is_journaled() {
+    let app = TestApp::start().await;
+
+    let running = submit(&app, video("long", 60)).await;
+    app.wait_for_status(running, JobStatus::Playing).await;
+    let queued = submit(&app, video("queued", 60)).await;
+    app.wait_for_status(queued, JobStatus::Ready).await;
+
+    let journal = std::fs::read_to_string(app.dir.path().join("queue.json")).unwrap();
+    let journal: serde_json::Value = serde_json::from_str(&journal).unwrap();
+    assert_eq!(journal["current"]["id"], running);
+    assert_eq!(journal["queue"][0]["id"], queued);
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn journaled_queue_is_restored_on_boot() {
+    let app = TestApp::start().await;
+
+    let running = submit(&app, video("long", 60)).await;
+    app.wait_for_status(running, JobStatus::Playing).await;
+    let first = submit(&app, video("first", 60)).await;
+    let second = submit(&app, video("second", 60)).await;
+    app.wait_for_status(second, JobStatus::Ready).await;
+
+    // stop the first server as if it had crashed, leaving its journal behind
+    let journal_file = app.dir.path().join("queue.json");
+    let journal = std::fs::read_to_string(&journal_file).unwrap();
+    app.clear().await;
+    std::fs::write(&journal_file, journal).unwrap();
+
+    let restored = TestApp::with_config(|config| {
+        config.queue_file = journal_file.clone();
+        config.history_file = app.dir.path().join("history.json");
+    })
+    .await;
+    // the interrupted job is put back in front and plays first
+    restored.wait_for_status(running, JobStatus::Playing).await;
+    assert_eq!(queue_ids(&restored).await, [first, second]);
+
+    let next = submit(&restored, video("next", 60)).await;
+    assert!(next > running && next > first && next > second);
+
+    restored.clear().await;
+}
+
+#[tokio::test]
+async fn stalled_yt_dlp_is_killed_and_fails_the_job() {
+    let app = TestApp::with_config(|config| config.yt_dlp.timeout_secs = 1).await;
+
+    let started = Instant::now();
+    let job_id = submit(&app, format!("{}&delay=30", video("stalled", 1))).await;
+    let job = app.wait_for_finished(job_id).await;
+
+    let JobStatus::Failed { code, .. } = job.status else {
+        panic!("expected job to fail, got {:?}", job.status);
+    };
+    assert_eq!(code, Some(ErrorCode::Timeout));
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[tokio::test]
+async fn jobs_wait_for_a_free_yt_dlp_slot() {
+    let app = TestApp::with_config(|config| config.yt_dlp.max_processes = 1).await;
+    let submit = |id: &str| submit(&app, format!("{}&delay=2", video(id, 60)));
+
+    let first = submit("first").await;
+    let second = submit("second").await;
+    app.wait_for_status(second, JobStatus::Waiting).await;
+    app.wait_for_status(first, JobStatus::Playing).await;
+    app.wait_for_status(second, JobStatus::Ready).await;
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn playback_does_not_wait_for_background_downloads() {
+    let app = TestApp::with_config(|config| {
+        config.yt_dlp.max_processes = 1;
+        config.subtitles.languages = vec!["en".into()];
+    })
+    .await;
+    let queue = &app.state.queue;
+
+    let running = submit(&app, video("long", 60)).await;
+    app.wait_for_status(running, JobStatus::Playing).await;
+    let next = submit(&app, format!("{}&subs=en", video("next", 60))).await;
+    app.wait_for_status(next, JobStatus::Ready).await;
+    // takes the only background slot for longer than the test waits
+    let download = queue
+        .submit(
+            JobType::QueueFile {
+                url: format!("{}&download_secs=30", video("download", 60)),
+                height: MinHeight(480),
+                title: String::new(),
+                file: Default::default(),
+            },
+            JobOptions::default(),
+        )
+        .await;
+    app.wait_for_status(download, JobStatus::Downloading).await;
+
+    // the subtitles of the next job are downloaded right before it plays
+    let skipped_at = Instant::now();
+    queue.cancel().await;
+    let subtitles = app.dir.path().join(format!("subtitles/job{next}.en.vtt"));
+    app.wait_for(|| async { subtitles.exists().then_some(()) })
+        .await;
+    assert!(skipped_at.elapsed() < Duration::from_secs(5));
+
+    app.clear().await;
+}
+
+#[tokio::test]
+async fn sponsorblock_segments_are_skipped() {
+    let app = TestApp::with_config(|config| config.sponsorblock.enabled = true).await;
+    let mut events = app.state.events.subscribe();
+
+    // outro is not skipped by default, so it is not even looked up
+    let url = format!(
+        "{}&segments=intro:0-5,sponsor:8-25,outro:28-30",
+        video("sponsored", 30)
+    );
+    let job_id = submit(&app, url).await;
+    let job = app.wait_for_status(job_id, JobStatus::Playing).await;
+    let categories: Vec<_> = job
+        .track_info
+        .segments
+        .iter()
+        .map(|segment| segment.category.as_str())
+        .collect();
+    assert_eq!(categories, ["intro", "sponsor"]);
+
+    let mut skipped = vec![];
+    while skipped.len() < 2 {
+        let event = tokio::time::timeout(Duration::from_secs(20), events.recv())
+            .await
+            .expect("timed out waiting for skipped segments")
+            .unwrap();
+        if let Event::SegmentSkipped {
+            job_id: id,
+            segment,
+        } = event
+        {
+            assert_eq!(id, job_id);
+            skipped.push((segment.category, segment.end));
+        }
+    }
+    assert_eq!(skipped, [("intro".into(), 5), ("sponsor".into(), 25)]);
+    // skipping to the end of a segment does not count as the player stopping early
+    app.wait_for_status(job_id, JobStatus::Completed).await;
+}
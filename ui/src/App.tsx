@@ -1,24 +1,21 @@
 import { Queue } from "./components/queue";
 import { Form } from "./components/form";
 import { useMutation, useQueryClient } from "@tanstack/react-query";
-import type { VideoType } from "./types/inspect";
+import type { QueueArgs } from "./types/inspect";
 import { toast } from "sonner";
 import { History } from "./components/history";
 
 export default function App() {
   const queryClient = useQueryClient();
   const mutation = useMutation({
-    mutationFn: async ([video_type, url, min_height]: [
-      VideoType,
-      string,
-      number
-    ]) => {
+    mutationFn: async ([video_type, url, min_height, start]: QueueArgs) => {
       const fragment = video_type === "merged" ? "queue_merged" : "queue_split";
       const resp = await fetch(`/api/${fragment}`, {
         method: "POST",
         body: JSON.stringify({
           url,
           height: min_height,
+          start,
         }),
         headers: {
           "Content-Type": "application/json",
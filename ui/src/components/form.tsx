@@ -9,7 +9,7 @@ import {
   SelectTrigger,
   SelectValue,
 } from "./ui/select";
-import type { VideoType } from "@/types/inspect";
+import type { QueueArgs } from "@/types/inspect";
 
 const QUALITY_TO_MIN_HEIGHT = {
   sd: 480,
@@ -24,7 +24,7 @@ export function Form({
   mutation,
 }: {
   // eslint-disable-next-line @typescript-eslint/no-explicit-any
-  mutation: UseMutationResult<any, Error, [VideoType, string, number], unknown>;
+  mutation: UseMutationResult<any, Error, QueueArgs, unknown>;
 }) {
   const handleSubmit = (e: FormEvent<HTMLFormElement>) => {
     e.preventDefault();
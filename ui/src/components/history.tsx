@@ -1,8 +1,8 @@
 import { formatTime, getRelativeTimeString } from "@/lib/format-time";
-import type { HistoryEntry, VideoType } from "@/types/inspect";
+import type { HistoryEntry, QueueArgs } from "@/types/inspect";
 import { useQuery, type UseMutationResult } from "@tanstack/react-query";
 import clsx from "clsx";
-import { ChevronDown, ListEnd, Trash } from "lucide-react";
+import { ChevronDown, ListEnd, StepForward, Trash } from "lucide-react";
 import { useLocalStorage } from "@uidotdev/usehooks";
 import { Button } from "./ui/button";
 import {
@@ -17,7 +17,7 @@ export function History({
   mutation,
 }: {
   // eslint-disable-next-line @typescript-eslint/no-explicit-any
-  mutation: UseMutationResult<any, Error, [VideoType, string, number], unknown>;
+  mutation: UseMutationResult<any, Error, QueueArgs, unknown>;
 }) {
   const [open, setOpen] = useLocalStorage("historyOpen", false);
   return (
@@ -46,7 +46,7 @@ function HistoryContainer({
   mutation,
 }: {
   // eslint-disable-next-line @typescript-eslint/no-explicit-any
-  mutation: UseMutationResult<any, Error, [VideoType, string, number], unknown>;
+  mutation: UseMutationResult<any, Error, QueueArgs, unknown>;
 }) {
   const { data } = useQuery({
     queryKey: ["history"],
@@ -85,17 +85,31 @@ function HistoryContainer({
             <DropdownMenuContent align="end">
               <DropdownMenuItem
                 onClick={() =>
-                  // TODO: always queue split and used height
                   mutation.mutate([
-                    "split",
+                    entry.track_type,
                     entry.webpage_url,
-                    entry.height ?? 720,
+                    entry.height ?? undefined,
                   ])
                 }
               >
                 <ListEnd className="mr-1" />
                 Add to queue
               </DropdownMenuItem>
+              {entry.position !== null && (
+                <DropdownMenuItem
+                  onClick={() =>
+                    mutation.mutate([
+                      entry.track_type,
+                      entry.webpage_url,
+                      entry.height ?? undefined,
+                      entry.position ?? undefined,
+                    ])
+                  }
+                >
+                  <StepForward className="mr-1" />
+                  Resume from {formatTime(entry.position)}
+                </DropdownMenuItem>
+              )}
               <DropdownMenuItem
                 variant="destructive"
                 onClick={() => removeHistoryMutation.mutate(entry.webpage_url)}
@@ -99,6 +99,7 @@ export function NowPlaying({
           <PlayerControls
             jobId={item?.job_id ?? null}
             playerState={playerState?.state ?? null}
+            hasChapters={!!info?.chapters?.length}
           />
         </div>
       </div>
@@ -7,15 +7,18 @@ import {
   Pause,
   FastForward,
   SkipForward,
+  Captions,
 } from "lucide-react";
 import { Button } from "./ui/button";
 
 export function PlayerControls({
   jobId,
   playerState,
+  hasChapters,
 }: {
   jobId: string | null;
   playerState: "playing" | "paused" | null;
+  hasChapters: boolean;
 }) {
   const mutation = useMutation({
     mutationFn: (job_id: string) => {
@@ -29,11 +32,23 @@ export function PlayerControls({
       mutation.mutate(jobId);
     }
   };
-  const { seekForward, seekRewind, togglePause } = usePlayerCommandsMutation();
+  const {
+    seekForward,
+    seekRewind,
+    togglePause,
+    cycleSubtitles,
+    previousChapter,
+  } = usePlayerCommandsMutation();
 
   return (
     <div className="flex justify-center items-center mt-1">
-      <Button variant="ghost" size="icon" className="size-12" disabled>
+      <Button
+        variant="ghost"
+        size="icon"
+        className="size-12 cursor-pointer"
+        disabled={!jobId || !playerState || !hasChapters}
+        onClick={previousChapter}
+      >
         <SkipBack className="size-5 fill-inherit" />
       </Button>
       <Button
@@ -76,6 +91,15 @@ export function PlayerControls({
       >
         <SkipForward className="size-5 fill-inherit" />
       </Button>
+      <Button
+        variant="ghost"
+        size="icon"
+        className="size-12 cursor-pointer"
+        disabled={!jobId || !playerState}
+        onClick={cycleSubtitles}
+      >
+        <Captions className="size-5" />
+      </Button>
     </div>
   );
 }
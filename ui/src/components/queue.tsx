@@ -181,7 +181,11 @@ export function QueueItem({ item }: { item: InspectItem | null }) {
         )}
         {info && (
           <p className="absolute right-1 bottom-1 text-xs text-white/80 border border-black/20 rounded-sm px-0.5 bg-black/50 ">
-            {formatTime(info.duration)}
+            {item?.clip
+              ? `${formatTime(item.clip.start)}–${formatTime(
+                  item.clip.end ?? info.duration
+                )}`
+              : formatTime(info.duration)}
           </p>
         )}
       </div>
@@ -6,7 +6,12 @@ type Command =
   | { SeekTo: number }
   | "TogglePause"
   | "Mute"
-  | "FullVolume";
+  | "FullVolume"
+  | "CycleSubtitles"
+  | "DisableSubtitles"
+  | "NextChapter"
+  | "PreviousChapter"
+  | { SeekToChapter: number };
 
 export function usePlayerCommandsMutation() {
   const queryClient = useQueryClient();
@@ -35,6 +40,14 @@ export function usePlayerCommandsMutation() {
     });
   const mute = () => commandMutation.mutate("Mute");
   const fullVolume = () => commandMutation.mutate("FullVolume");
+  const cycleSubtitles = () => commandMutation.mutate("CycleSubtitles");
+  const disableSubtitles = () => commandMutation.mutate("DisableSubtitles");
+  const nextChapter = () => commandMutation.mutate("NextChapter");
+  const previousChapter = () => commandMutation.mutate("PreviousChapter");
+  const seekToChapter = (index: number) =>
+    commandMutation.mutate({
+      SeekToChapter: index,
+    });
 
   return {
     seekForward,
@@ -43,6 +56,11 @@ export function usePlayerCommandsMutation() {
     seekTo,
     mute,
     fullVolume,
+    cycleSubtitles,
+    disableSubtitles,
+    nextChapter,
+    previousChapter,
+    seekToChapter,
   };
 }
 
@@ -1,22 +1,81 @@
 export type InspectApi = {
   now_playing: InspectItem | null;
   queue: InspectItem[];
+  finished: InspectItem[];
   player: PlayerState | null;
+  skip_votes_needed: number;
 };
 
 export type PlayerState = {
-  state: "playing" | "paused";
+  state: "playing" | "paused" | "stopped";
   time: number;
   length: number;
+  // percent, 100 is the unamplified level
   volume: number;
 };
 
 export type InspectItem = {
   job_id: string;
+  batch_id: number | null;
+  status: JobStatus;
+  queued_at: number;
+  started_at: number | null;
+  finished_at: number | null;
+  // null unless auth is enabled
+  submitter: string | null;
+  upvotes: number;
+  downvotes: number;
+  // only counted while the job is playing
+  skip_votes: number;
+  // only while a file is downloading
+  progress: DownloadProgress | null;
+  // null when the whole video is played
+  clip: Clip | null;
   current: boolean;
   track_info: TrackInfo;
 };
 
+// seconds into the video, a null end plays to the end
+export type Clip = {
+  start: number;
+  end: number | null;
+};
+
+export type DownloadProgress = {
+  percent: number | null;
+  downloaded_bytes: number;
+  total_bytes: number | null;
+  // bytes per second
+  speed: number | null;
+  // seconds
+  eta: number | null;
+};
+
+export type JobStatus =
+  | {
+      state:
+        | "waiting"
+        | "resolving"
+        | "downloading"
+        | "ready"
+        | "playing"
+        | "completed"
+        | "cancelled";
+    }
+  // code is only set when yt-dlp failed
+  | { state: "failed"; reason: string; code?: ErrorCode };
+
+// also returned as `code` next to `error` by the API
+export type ErrorCode =
+  | "unavailable"
+  | "private"
+  | "sign_in_required"
+  | "geo_blocked"
+  | "no_matching_format"
+  | "unsupported_url"
+  | "timeout"
+  | "failed";
+
 export type TrackInfo = {
   title: string;
   channel: string;
@@ -29,10 +88,59 @@ export type TrackInfo = {
   track_type: "merged" | "split";
   duration: number;
   webpage_url: string;
+  subtitles: SubtitleInfo[];
+  chapters: Chapter[];
+  // SponsorBlock segments, empty unless enabled on the server
+  segments: Segment[];
+};
+
+export type Segment = {
+  // e.g. "sponsor", "intro" or "selfpromo"
+  category: string;
+  start: number;
+  end: number;
+};
+
+export type Chapter = {
+  title: string;
+  // seconds into the video
+  start: number;
+  end: number;
+};
+
+export type SubtitleInfo = {
+  // as yt-dlp names it, e.g. "en", "en-US" or "en-orig"
+  lang: string;
+  name: string | null;
+  // automatic captions rather than written subtitles
+  auto: boolean;
 };
 
 export type HistoryEntry = TrackInfo & {
   inserted_at: number;
+  submitter: string | null;
+  // seconds playback stopped at, null if the video starts over
+  position: number | null;
+};
+
+export type Identity = {
+  name: string | null;
+  role: Role;
+};
+
+export type Role = "guest" | "member" | "admin";
+
+export type SearchResult = {
+  title: string;
+  channel: string;
+  // null for live streams
+  duration: number | null;
+  thumbnail: string;
+  webpage_url: string;
 };
 
 export type VideoType = "merged" | "split";
+
+// what is queued: stream type, url, min height (the server's default if undefined) and
+// optionally the second to start at
+export type QueueArgs = [VideoType, string, number | undefined, number?];